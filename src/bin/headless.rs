@@ -0,0 +1,196 @@
+// Headless runner: simulates a match from a given seed on the host backend, feeding it a scripted
+// gamepad sequence, and dumps selected frames as PNGs (using the cart's palette)
+//
+// Usage:
+//   cargo run --features headless --target <host triple> -- [OPTIONS]
+//
+// Options:
+//   --seed <N>       map seed (default 1, the seed of a fresh disk)
+//   --frames <N>     number of frames to simulate (default 60)
+//   --dump <LIST>    comma separated frame indices to save (default: the last frame)
+//   --input <FILE>   gamepad script (default: no input)
+//   --out <DIR>      output directory (default ".")
+//   --scale <N>      integer upscaling of the saved PNGs (default 1)
+//   --disk <FILE>    boot from this disk image like the cart does (ignores --seed), and write
+//                    the disk back when done. The cart boots to the title screen, so the script
+//                    has to go through the menus to start a match
+//   --replay         play back the match recorded on the disk (needs --disk, ignores --input)
+//                    A recording that runs out before the match is over goes back to the title screen
+//
+// Gamepad script: one entry per line, `<frame> <player 1 buttons> <player 2 buttons>`
+// Buttons are held from that frame until the next entry. Use `x` (button 1), `z` (button 2),
+// `l`, `r`, `u`, `d` for the d-pad, or `-` for nothing. Lines starting with `#` are ignored
+//
+//   # p1 walks right for half a second then summons, p2 cycles its class
+//   0   r  -
+//   30  -  z
+//   31  x  -
+
+use cart::wasm4::{self, host, SCREEN_SIZE};
+use cart::Game;
+use std::{error::Error, fs, io::BufWriter, path::PathBuf};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+struct Options {
+    seed: u64,
+    frames: u32,
+    dump: Vec<u32>,
+    input: Option<PathBuf>,
+    out: PathBuf,
+    scale: u32,
+    disk: Option<PathBuf>,
+    replay: bool,
+}
+
+fn parse_options() -> Result<Options> {
+    let mut options = Options {
+        seed: 1,
+        frames: 60,
+        dump: Vec::new(),
+        input: None,
+        out: PathBuf::from("."),
+        scale: 1,
+        disk: None,
+        replay: false,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--seed" => options.seed = value()?.parse()?,
+            "--frames" => options.frames = value()?.parse()?,
+            "--dump" => {
+                options.dump = value()?
+                    .split(',')
+                    .map(|frame| frame.trim().parse())
+                    .collect::<std::result::Result<_, _>>()?
+            }
+            "--input" => options.input = Some(value()?.into()),
+            "--out" => options.out = value()?.into(),
+            "--scale" => options.scale = value()?.parse::<u32>()?.max(1),
+            "--disk" => options.disk = Some(value()?.into()),
+            "--replay" => options.replay = true,
+            _ => return Err(format!("unknown argument {arg}").into()),
+        }
+    }
+
+    if options.replay && options.disk.is_none() {
+        return Err("--replay needs a --disk to play from".into());
+    }
+
+    if options.dump.is_empty() {
+        options.dump.push(options.frames.saturating_sub(1));
+    }
+
+    Ok(options)
+}
+
+// Parses the gamepad script into a list of (start frame, gamepads), sorted by frame
+fn parse_script(source: &str) -> Result<Vec<(u32, [u8; 2])>> {
+    fn buttons(token: &str) -> Result<u8> {
+        token.chars().try_fold(0u8, |buttons, c| {
+            Ok(buttons
+                | match c {
+                    'x' => wasm4::BUTTON_1,
+                    'z' => wasm4::BUTTON_2,
+                    'l' => wasm4::BUTTON_LEFT,
+                    'r' => wasm4::BUTTON_RIGHT,
+                    'u' => wasm4::BUTTON_UP,
+                    'd' => wasm4::BUTTON_DOWN,
+                    '-' => 0,
+                    _ => return Err(format!("unknown button '{c}'").into()),
+                })
+        })
+    }
+
+    let mut entries = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let [frame, p1, p2] = tokens[..] else {
+            return Err(format!("line {}: expected `<frame> <p1> <p2>`", number + 1).into());
+        };
+
+        entries.push((frame.parse()?, [buttons(p1)?, buttons(p2)?]));
+    }
+
+    entries.sort_by_key(|(frame, _)| *frame);
+    Ok(entries)
+}
+
+fn save_png(path: &PathBuf, scale: u32) -> Result<()> {
+    let palette = host::palette();
+    let size = SCREEN_SIZE * scale;
+
+    let mut data = Vec::with_capacity((size * size * 3) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let color = palette[host::pixel(x / scale, y / scale) as usize];
+            data.extend_from_slice(&color.to_be_bytes()[1..]);
+        }
+    }
+
+    let file = fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size, size);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let options = parse_options()?;
+    let script = match &options.input {
+        Some(_) if options.replay => Vec::new(),
+        Some(path) => parse_script(&fs::read_to_string(path)?)?,
+        None => Vec::new(),
+    };
+    fs::create_dir_all(&options.out)?;
+
+    host::reset();
+    let mut game = match &options.disk {
+        Some(path) => {
+            // a missing image is a fresh disk
+            if let Ok(bytes) = fs::read(path) {
+                host::set_disk(&bytes);
+            }
+            unsafe { Game::new() }
+        }
+        None => unsafe { Game::with_seed(options.seed) },
+    };
+
+    if options.replay && !game.replay_last_match() {
+        return Err("no recording on the disk".into());
+    }
+
+    let mut gamepads = [0u8; 2];
+    for frame in 0..options.frames {
+        // latest script entry that started at or before this frame
+        if let Some((_, buttons)) = script.iter().rev().find(|(start, _)| *start <= frame) {
+            gamepads = *buttons;
+        }
+        host::set_gamepad(0, gamepads[0]);
+        host::set_gamepad(1, gamepads[1]);
+
+        host::begin_frame();
+        unsafe { game.run() };
+
+        if options.dump.contains(&frame) {
+            let path = options.out.join(format!("frame_{frame:05}.png"));
+            save_png(&path, options.scale)?;
+            println!("saved {}", path.display());
+        }
+    }
+
+    if let Some(path) = &options.disk {
+        fs::write(path, host::disk())?;
+    }
+
+    Ok(())
+}
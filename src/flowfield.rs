@@ -0,0 +1,224 @@
+// Flow fields: for a given goal, the direction to walk in from every cell of the board
+//
+// Every unit heading to the same goal samples the same field, so we run a single Dijkstra (from the
+// goal outwards) instead of an A* per unit. Fields are cached and only thrown away once the layout of
+// the board changes. Directions are packed as nibbles, so a field is 450 bytes of heap
+
+use crate::pathfinding::{self, nibble, set_nibble, Goal, Nibbles, OpenList, MAX_GOALS};
+use crate::simulation::Fnv;
+use crate::*;
+use std::hash::{Hash, Hasher};
+
+// Max number of cached fields, the least recently used one gets replaced
+const MAX_FLOW_FIELDS: usize = 4;
+
+// Nibbles values besides the 8 directions of get_neighbours
+const AT_GOAL: u8 = 8;
+const UNREACHABLE: u8 = 9;
+
+#[derive(Clone)]
+struct FlowField {
+    goal: Goal,
+    directions: Box<Nibbles>,
+    last_used: u16,
+}
+
+impl FlowField {
+    fn direction(&self, index: u16) -> u8 {
+        nibble(&self.directions, index)
+    }
+
+    fn compute(board: &Board, goal: Goal) -> Self {
+        let mut directions: Box<Nibbles> = Box::new([UNREACHABLE | UNREACHABLE << 4; AREA / 2]);
+        let mut set = |index: u16, direction: u8| set_nibble(&mut directions, index, direction);
+
+        let mut costs = [u16::MAX; AREA];
+        let mut open = OpenList::new();
+
+        let mut goals = [u16::MAX; MAX_GOALS];
+        let count = pathfinding::goal_cells(board, goal, &mut goals);
+        for cell in goals[..count].iter() {
+            costs[*cell as usize] = 0;
+            set(*cell, AT_GOAL);
+            open.push(0, *cell);
+        }
+
+        while let Some((cost, current)) = open.pop() {
+            // we already found a cheaper way to this cell since it was pushed
+            if cost > costs[current as usize] {
+                continue;
+            }
+
+            // paths are symmetric, so whoever can step to us can walk back the other way
+            for (direction, neighbour) in pathfinding::walkable_neighbours(board, current) {
+                let new_cost = cost + pathfinding::step_cost(direction);
+                if new_cost < costs[neighbour as usize] {
+                    costs[neighbour as usize] = new_cost;
+                    set(neighbour, ((direction + 4) % 8) as u8);
+                    open.push(new_cost, neighbour);
+                }
+            }
+        }
+
+        Self {
+            goal,
+            directions,
+            last_used: 0,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct FlowFields {
+    fields: Vec<FlowField>,
+
+    // hash of the board layout the fields were computed for
+    layout: u64,
+    clock: u16,
+}
+
+impl FlowFields {
+    // Drops every field if the layout of the board changed since they were computed
+    // Only walkable cells and building footprints matter, so a church counting down doesn't count
+    pub fn invalidate_if_changed(&mut self, board: &Board) {
+        let mut hasher = Fnv::default();
+        for cell in board.iter() {
+            building_dimensions(cell).hash(&mut hasher);
+        }
+
+        let layout = hasher.finish();
+        if layout != self.layout {
+            self.fields.clear();
+            self.layout = layout;
+        }
+    }
+
+    // Cell to walk to from `index` to get closer to the goal
+    // Returns Some(index) if we're already there, and None if the goal can't be reached
+    pub fn next_step(&mut self, board: &Board, index: u16, goal: Goal) -> Option<u16> {
+        // any sub-cell of the same building shares one field
+        let goal = match goal {
+            Goal::Building(cell) => match building_dimensions(&board[cell as usize]) {
+                Some((width, _, j)) => {
+                    let (x, y) = calculate_big_sprite_root_position(width, j, cell);
+                    Goal::Building(grid_from_vec(x, y))
+                }
+                None => return None,
+            },
+            goal => goal,
+        };
+
+        self.clock = self.clock.wrapping_add(1);
+        let slot = match self.fields.iter().position(|field| field.goal == goal) {
+            Some(slot) => slot,
+            None if self.fields.len() < MAX_FLOW_FIELDS => {
+                self.fields.push(FlowField::compute(board, goal));
+                self.fields.len() - 1
+            }
+            None => {
+                let clock = self.clock;
+                let (slot, _) = self
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, field)| clock.wrapping_sub(field.last_used))
+                    .unwrap();
+                self.fields[slot] = FlowField::compute(board, goal);
+                slot
+            }
+        };
+
+        let field = &mut self.fields[slot];
+        field.last_used = self.clock;
+
+        match field.direction(index) {
+            AT_GOAL => Some(index),
+            UNREACHABLE => None,
+            direction => {
+                let (x, y) = vec_from_grid(index);
+                let (offset_x, offset_y) = NEIGHBOUR_OFFSETS[direction as usize];
+                Some(grid_from_vec(x.wrapping_add_signed(offset_x), y.wrapping_add_signed(offset_y)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Walks from a cell until we reach the goal, returning the cost of the path
+    fn walk(mut step: impl FnMut(u16) -> Option<u16>, start: u16) -> Option<u16> {
+        let mut current = start;
+        let mut cost = 0;
+        loop {
+            let next = step(current)?;
+            if next == current {
+                return Some(cost);
+            }
+
+            let (ax, ay) = vec_from_grid(current);
+            let (bx, by) = vec_from_grid(next);
+            cost += if ax != bx && ay != by { pathfinding::DIAGONAL_COST } else { pathfinding::STRAIGHT_COST };
+            assert!(cost < u16::MAX / 2, "path is looping");
+            current = next;
+        }
+    }
+
+    #[test]
+    fn matches_astar_on_generated_maps() {
+        for seed in 0..4 {
+            let board = terrain::generate(&mut fastrand::Rng::with_seed(seed), 0);
+            let church = board.iter().position(|cell| matches!(cell, CellState::Church(..))).unwrap() as u16;
+            let goals = [Goal::Building(church), Goal::Cell(grid_from_vec(0, 0)), Goal::Cell(grid_from_vec(15, 29))];
+
+            let mut fields = FlowFields::default();
+            for goal in goals {
+                for start in (0..AREA as u16).step_by(5) {
+                    if !matches!(board[start as usize], CellState::Empty) {
+                        continue;
+                    }
+
+                    let flow = walk(|index| fields.next_step(&board, index, goal), start);
+                    let astar = walk(|index| pathfinding::next_step(&board, index, goal), start);
+                    assert_eq!(flow, astar, "seed {seed}, start {:?}", vec_from_grid(start));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalidated_when_the_layout_changes() {
+        let mut board: Board = Box::new([CellState::Empty; AREA]);
+        let mut fields = FlowFields::default();
+        let goal = Goal::Cell(grid_from_vec(10, 5));
+        fields.invalidate_if_changed(&board);
+        assert_eq!(fields.next_step(&board, grid_from_vec(5, 5), goal), Some(grid_from_vec(6, 5)));
+
+        // a church burning down doesn't change anything
+        board[grid_from_vec(6, 5) as usize] = CellState::Church(BuildingState::Burning, 0, 5);
+        fields.invalidate_if_changed(&board);
+        assert_eq!(fields.fields.len(), 0);
+        assert_ne!(fields.next_step(&board, grid_from_vec(5, 5), goal), Some(grid_from_vec(6, 5)));
+        board[grid_from_vec(6, 5) as usize] = CellState::Church(BuildingState::Burning, 0, 4);
+        fields.invalidate_if_changed(&board);
+        assert_eq!(fields.fields.len(), 1);
+
+        board[grid_from_vec(6, 5) as usize] = CellState::Church(BuildingState::Destroyed, 0, 0);
+        fields.invalidate_if_changed(&board);
+        assert_eq!(fields.fields.len(), 1);
+    }
+
+    #[test]
+    fn sub_cells_of_a_building_share_a_field() {
+        let mut board: Board = Box::new([CellState::Empty; AREA]);
+        for j in 0..4 {
+            board[grid_from_vec(10 + j % 2, 10 + j / 2) as usize] = CellState::House(BuildingState::Solid, j, HOUSE_INTEGRITY);
+        }
+
+        let mut fields = FlowFields::default();
+        fields.next_step(&board, 0, Goal::Building(grid_from_vec(10, 10)));
+        fields.next_step(&board, 0, Goal::Building(grid_from_vec(11, 11)));
+        assert_eq!(fields.fields.len(), 1);
+    }
+}
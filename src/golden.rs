@@ -0,0 +1,328 @@
+// Golden-frame regression tests for the renderer, running on the host backend
+//
+// Every test builds a specific board / entity layout, renders it through the real `draw_*` calls
+// and compares the framebuffer with the reference checked in under `tests/golden/`
+// References are 2-bit grayscale PNGs (palette index 0 = white ... 3 = black), so they are exact
+// copies of the framebuffer and can still be viewed in review
+//
+// To regenerate the references after an intended change:
+//   GOLDEN_UPDATE=1 cargo test --target x86_64-unknown-linux-gnu golden
+//
+// On a mismatch, `<name>.actual.png` and `<name>.diff.png` (mismatching pixels in red) are written
+// to `target/golden/`
+
+use crate::wasm4::{host, SCREEN_SIZE};
+use crate::simulation::tests::{place, spawn};
+use crate::*;
+use std::{fs, io::BufWriter, path::PathBuf, sync::{Mutex, MutexGuard}};
+
+// The host memory is global, so only one test can render at a time
+static FRAMEBUFFER_LOCK: Mutex<()> = Mutex::new(());
+
+// A failing test poisons the lock, which shouldn't fail the other ones
+pub(crate) fn lock() -> MutexGuard<'static, ()> {
+    FRAMEBUFFER_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+const SIZE: usize = SCREEN_SIZE as usize;
+
+fn reference_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+// Palette indices of the whole screen, row by row
+fn screen() -> Vec<u8> {
+    (0..SIZE * SIZE)
+        .map(|i| host::pixel((i % SIZE) as u32, (i / SIZE) as u32))
+        .collect()
+}
+
+fn write_png(path: &PathBuf, color: png::ColorType, depth: png::BitDepth, data: &[u8]) {
+    let file = fs::File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), SIZE as u32, SIZE as u32);
+    encoder.set_color(color);
+    encoder.set_depth(depth);
+    encoder.write_header().unwrap().write_image_data(data).unwrap();
+}
+
+// Packs palette indices into a 2-bit grayscale image
+fn write_reference(path: &PathBuf, pixels: &[u8]) {
+    let packed: Vec<u8> = pixels
+        .chunks(4)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, index)| byte | ((3 - index) << (6 - i * 2)))
+        })
+        .collect();
+    write_png(path, png::ColorType::Grayscale, png::BitDepth::Two, &packed);
+}
+
+fn read_reference(path: &PathBuf) -> Option<Vec<u8>> {
+    let mut decoder = png::Decoder::new(fs::File::open(path).ok()?);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0u8; reader.output_buffer_size()];
+    reader.next_frame(&mut data).unwrap();
+
+    // expanded to 8 bits: 0, 85, 170, 255
+    Some(data.iter().take(SIZE * SIZE).map(|level| 3 - level / 85).collect())
+}
+
+// Writes the actual frame and a diff image (red where the pixels differ, faded expected elsewhere)
+fn write_failure(name: &str, actual: &[u8], expected: &[u8]) {
+    let dir = output_dir();
+    fs::create_dir_all(&dir).unwrap();
+    write_reference(&dir.join(format!("{name}.actual.png")), actual);
+
+    let diff: Vec<u8> = actual
+        .iter()
+        .zip(expected)
+        .flat_map(|(a, e)| {
+            if a == e {
+                let faded = 160 + (3 - e) * 30;
+                [faded, faded, faded]
+            } else {
+                [255, 0, 0]
+            }
+        })
+        .collect();
+    write_png(&dir.join(format!("{name}.diff.png")), png::ColorType::Rgb, png::BitDepth::Eight, &diff);
+}
+
+// Renders with the given closure and compares against (or regenerates) the reference
+fn check(name: &str, game: &mut Game, render: impl FnOnce(&mut Game)) {
+    host::begin_frame();
+    render(game);
+    let actual = screen();
+
+    let path = reference_dir().join(format!("{name}.png"));
+    if std::env::var_os("GOLDEN_UPDATE").is_some() {
+        fs::create_dir_all(reference_dir()).unwrap();
+        write_reference(&path, &actual);
+        return;
+    }
+
+    let expected = read_reference(&path)
+        .unwrap_or_else(|| panic!("missing reference {path:?}, run with GOLDEN_UPDATE=1"));
+    let mismatches = actual.iter().zip(&expected).filter(|(a, e)| a != e).count();
+    if mismatches > 0 {
+        write_failure(name, &actual, &expected);
+        panic!("{name}: {mismatches} pixels differ, see {:?}", output_dir());
+    }
+}
+
+// Game on a fixed seed with an empty board, camera at the top left, nothing selected
+fn empty_game() -> Game {
+    host::reset();
+    let mut game = unsafe { Game::with_seed(0x5eed) };
+    game.sim.grid.fill(CellState::Empty);
+    game.sim.view_local_cameras = [(0, 0); 2];
+    game.sim.cursors = [0; 2];
+    game
+}
+
+// A bit of everything the grid can hold, in all of their building states
+fn village(game: &mut Game) {
+    place(&mut game.sim, 0, 0, 2, 2, |i| CellState::House(BuildingState::Solid, i, HOUSE_INTEGRITY));
+    place(&mut game.sim, 2, 0, 2, 2, |i| CellState::House(BuildingState::Burning, i, 5));
+    place(&mut game.sim, 4, 0, 2, 2, |i| CellState::House(BuildingState::Destroyed, i, 0));
+    place(&mut game.sim, 6, 0, 2, 2, |i| CellState::House2(BuildingState::Solid, i, HOUSE_INTEGRITY));
+    place(&mut game.sim, 8, 0, 2, 2, |i| CellState::House2(BuildingState::Burning, i, 5));
+    place(&mut game.sim, 10, 0, 2, 2, |i| CellState::House2(BuildingState::Destroyed, i, 0));
+    place(&mut game.sim, 0, 2, 2, 3, |i| CellState::Church(BuildingState::Solid, i, CHURCH_INTEGRITY));
+    place(&mut game.sim, 2, 2, 2, 3, |i| CellState::Church(BuildingState::Burning, i, 5));
+    place(&mut game.sim, 4, 2, 2, 3, |i| CellState::Church(BuildingState::Destroyed, i, 0));
+    place(&mut game.sim, 6, 2, 2, 2, CellState::BigRock);
+    place(&mut game.sim, 8, 2, 2, 2, |i| CellState::Tree(i, 0));
+    place(&mut game.sim, 10, 2, 2, 2, |i| CellState::Stand(i, 0));
+    place(&mut game.sim, 12, 0, 1, 2, CellState::Lamppost);
+    place(&mut game.sim, 13, 0, 1, 1, |_| CellState::Rock);
+    place(&mut game.sim, 14, 0, 1, 1, |_| CellState::Bell(BuildingState::Solid, BELL_INTEGRITY));
+    place(&mut game.sim, 14, 1, 1, 1, |_| CellState::Bell(BuildingState::Destroyed, 0));
+    place(&mut game.sim, 6, 4, 2, 1, |i| CellState::Farm(i, FARM_RIPE, 0));
+    place(&mut game.sim, 10, 4, 2, 1, |i| CellState::Farm(i, 0, 0));
+    place(&mut game.sim, 8, 4, 2, 1, |i| CellState::Hay(i, 0));
+    place(&mut game.sim, 13, 1, 1, 1, |_| CellState::Barricade(0));
+}
+
+#[test]
+fn golden_sprites() {
+    let _lock = lock();
+    let mut game = empty_game();
+    village(&mut game);
+    check("sprites", &mut game, |game| unsafe {
+        game.draw_background();
+        game.draw_sprites();
+    });
+}
+
+// Every villager and illager, in all of their states, looking the same way
+fn roster(game: &mut Game, facing: Direction) {
+    let villagers = [
+        VillagerClan::Villager,
+        VillagerClan::Farmer(0),
+        VillagerClan::Smith(0),
+        VillagerClan::Golem(0, GolemState::Idle),
+        VillagerClan::Golem(0, GolemState::Attack),
+        VillagerClan::Golem(0, GolemState::Broken),
+    ];
+    for (i, clan) in villagers.into_iter().enumerate() {
+        spawn(&mut game.sim, EntityType::VillagerClan(clan), 10 + i as u16 * 15, 10);
+    }
+
+    let illagers = [
+        IllagerClan::Vindicator,
+        IllagerClan::Pillager,
+        IllagerClan::Evoker(0),
+        IllagerClan::Vex(0),
+    ];
+    for (i, clan) in illagers.into_iter().enumerate() {
+        spawn(&mut game.sim, EntityType::IllagerClan(clan, IllagerState::Idle), 10 + i as u16 * 15, 30);
+        spawn(&mut game.sim, EntityType::IllagerClan(clan, IllagerState::Action), 10 + i as u16 * 15, 45);
+    }
+
+    for entity in game.sim.entities.iter_mut() {
+        entity.facing = facing;
+    }
+}
+
+#[test]
+fn golden_entities() {
+    let _lock = lock();
+    let mut game = empty_game();
+    roster(&mut game, Direction::E);
+    game.sim.rally[0] = Some(grid_from_vec(12, 8));
+    game.sim.entities[0].selected = true;
+
+    // draw_entities relies on the draw colors set up by draw_sprites
+    check("entities", &mut game, |game| unsafe {
+        game.draw_background();
+        game.draw_sprites();
+        game.draw_entities();
+    });
+}
+
+#[test]
+fn golden_entities_facing_left() {
+    let _lock = lock();
+    let mut game = empty_game();
+    roster(&mut game, Direction::W);
+
+    check("entities_facing_left", &mut game, |game| unsafe {
+        game.draw_background();
+        game.draw_sprites();
+        game.draw_entities();
+    });
+}
+
+#[test]
+fn golden_cursors() {
+    let _lock = lock();
+    let mut game = empty_game();
+    village(&mut game);
+
+    // cursor on a church sub-cell, so the cursor spans the whole building
+    game.sim.cursors[0] = grid_from_vec(3, 3);
+    game.sim.action_possible[0] = true;
+    check("cursor_church", &mut game, |game| unsafe {
+        game.draw_background();
+        game.draw_sprites();
+        game.draw_cursors();
+    });
+
+    // cursor on an empty cell that we can't do anything on
+    game.sim.cursors[0] = grid_from_vec(5, 8);
+    game.sim.action_possible[0] = false;
+    check("cursor_empty", &mut game, |game| unsafe {
+        game.draw_background();
+        game.draw_sprites();
+        game.draw_cursors();
+    });
+}
+
+#[test]
+fn golden_footer() {
+    let _lock = lock();
+    let mut game = empty_game();
+    spawn(&mut game.sim, EntityType::VillagerClan(VillagerClan::Villager), 0, 0);
+
+    game.sim.current_selected_class[0] = 1;
+    check("footer_villagers", &mut game, |game| unsafe { game.draw_footer() });
+
+    game.current_player = 1;
+    game.sim.current_selected_class[1] = 4;
+    game.sim.button_held[1] = true;
+    check("footer_illagers", &mut game, |game| unsafe { game.draw_footer() });
+}
+
+#[test]
+fn golden_minimap() {
+    let _lock = lock();
+    let mut game = empty_game();
+    village(&mut game);
+    spawn(&mut game.sim, EntityType::VillagerClan(VillagerClan::Villager), 200, 200);
+    spawn(&mut game.sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 250, 100);
+    game.sim.rally[0] = Some(grid_from_vec(20, 15));
+
+    check("minimap", &mut game, |game| unsafe {
+        game.draw_footer();
+        game.draw_minimap();
+    });
+}
+
+#[test]
+fn golden_results() {
+    let _lock = lock();
+    let mut game = empty_game();
+    village(&mut game);
+    game.sim.outcome = Some(Outcome::ChurchesDestroyed);
+    game.sim.stats = MatchStats { spawned: [12, 30], lost: [9, 17], emeralds_earned: [45, 128], buildings_burned: 6 };
+
+    check("results", &mut game, |game| unsafe {
+        game.draw_background();
+        game.draw_sprites();
+        game.draw_footer();
+        game.draw_results(Outcome::ChurchesDestroyed);
+    });
+}
+
+#[test]
+fn golden_title() {
+    let _lock = lock();
+    let mut game = empty_game();
+    village(&mut game);
+    game.menu = 2;
+    game.settings.debug_palette = true;
+
+    check("title", &mut game, |game| unsafe {
+        game.draw_background();
+        game.draw_sprites();
+        game.draw_footer();
+        game.draw_minimap();
+        game.draw_title();
+        game.debug_palette();
+    });
+}
+
+#[test]
+fn golden_setup() {
+    let _lock = lock();
+    let mut game = empty_game();
+    village(&mut game);
+    game.menu = 3;
+    game.setup = MatchSetup { mode: Mode::Ai, map_size: MapSize::Small, emeralds: STARTING_EMERALDS[2], ..game.setup };
+
+    check("setup", &mut game, |game| unsafe {
+        game.draw_background();
+        game.draw_sprites();
+        game.draw_footer();
+        game.draw_minimap();
+        game.draw_setup();
+    });
+}
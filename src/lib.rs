@@ -1,19 +1,27 @@
+// the buddy heap is sized for the 64 KB of WASM-4 memory, the host keeps its own allocator
+#[cfg(all(feature = "buddy-alloc", target_arch = "wasm32"))]
 mod alloc;
+mod flowfield;
+pub mod pathfinding;
+pub mod replay;
+pub mod save;
 mod sprites;
+mod simulation;
 mod terrain;
-mod wasm4;
+pub mod wasm4;
+pub use simulation::{MapSize, MatchSetup, MatchStats, Mode, Outcome, Simulation, VictoryConditions};
 pub use sprites::*;
+use replay::{Recording, Replay};
+use save::{Save, Settings};
 use std::{mem::transmute, cell::Cell, ops::Range};
 use wasm4::*;
 mod sound;
 pub use sound::*;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod golden;
 
 static mut GAME: Option<Game> = None;
 
-// Debug constants
-const DEBUG_PALETTE: bool = false;
-const MULTIPLAYER: bool = false;
-
 // Price constants
 const VINDICATOR: u8 = 1;
 const VILLAGER: u8 = VINDICATOR;
@@ -34,30 +42,154 @@ pub const GRID_LOCAL_SIZE_Y: u8 = 12;
 // Gameplay constant
 pub const FRAMES_PER_PARTICLE_TICK: u8 = 4;
 pub const CURSOR_MOVEMENT_SPEED_INV: u8 = 7;
-pub const CHURCH_EXPLOSION_FREQUENCY_THING: u8 = 20;
 pub const MAX_PARTICLE_COUNT: usize = 300;
 
+// Integrity of fresh buildings, burning ones lose a point once every BURN_INTERVAL frames
+pub const HOUSE_INTEGRITY: u8 = 10;
+pub const CHURCH_INTEGRITY: u8 = 10;
+pub const BELL_INTEGRITY: u8 = 6;
+pub const BURN_INTERVAL: u8 = 20;
+
+// Every BURN_INTERVAL frames, fire has a 1 in this chance to spread to each flammable neighbour of a burning cell
+pub const FIRE_SPREAD_CHANCE_INV: u8 = 12;
+
+// How many BURN_INTERVALs trees, hay, stands and farms burn for before there's nothing left
+pub const TREE_FUEL: u8 = 6;
+pub const HAY_FUEL: u8 = 3;
+pub const STAND_FUEL: u8 = 5;
+pub const BARRICADE_FUEL: u8 = 4;
+pub const FARM_FUEL: u8 = 2;
+
+// Entities closer than this (in pixels, on both axes) push each other away
+pub const SEPARATION_DISTANCE: i16 = 6;
+pub const SEPARATION_STRENGTH: i8 = 3;
+
+// Idle entities change their mind about where to stroll once every this many frames (on average)
+pub const WANDER_CHANCE_INV: u8 = 48;
+
+// Frames the attack sprite (illager action, golem attack) stays up after a hit
+pub const ATTACK_ANIMATION_FRAMES: u8 = 12;
+pub const DEATH_PARTICLE_COUNT: u8 = 8;
+
+// How far (in pixels, on both axes) vindicators spot villagers and golems to go after
+pub const VINDICATOR_SIGHT: u16 = 50;
+
+// Pillagers spot their targets from further away, and back off when they get closer than this
+pub const PILLAGER_SIGHT: u16 = 60;
+pub const PILLAGER_MIN_DISTANCE: u16 = 20;
+
+// Crossbow bolts speed (1/16th of a pixel per frame) and how long they fly before dropping
+pub const BOLT_SPEED: i32 = 40;
+pub const BOLT_LIFETIME: u8 = 24;
+
+// Evokers cast a spell once every this many frames, when they see someone to cast it on
+// Villagers close enough get a line of fangs, the ones further away get a visit from some vex
+pub const EVOKER_SPELL_COOLDOWN: u8 = 180;
+pub const EVOKER_SIGHT: u16 = 70;
+pub const FANG_RANGE: u16 = 40;
+pub const FANG_COUNT: i32 = 6;
+pub const FANG_SPACING: i32 = 8;
+pub const FANG_DAMAGE: u8 = 3;
+pub const VEX_PER_SPELL: u8 = 2;
+pub const VEX_LIFETIME: u8 = 240;
+pub const VEX_SIGHT: u16 = 80;
+
+// Golems go after the illagers they can see
+pub const GOLEM_SIGHT: u16 = 50;
+
+// Smiths at their workshop (a stand or the bell) put in a unit of work once every interval
+// Enough work makes a golem, a broken golem needs a bit less to stand up again
+pub const SMITH_WORK_INTERVAL: u8 = 15;
+pub const GOLEM_WORK: u8 = 120;
+pub const GOLEM_REPAIR: u8 = 40;
+pub const SMITH_SIGHT: u16 = 80;
+
+// Smiths rebuild the ruins they see, one point of integrity per unit of work, for a fee paid up front
+pub const REBUILD_COST: u8 = 10;
+
+// Villagers throw a bucket on the burning buildings they see once every interval, with a chance of
+// putting the fire out
+pub const VILLAGER_SIGHT: u16 = 60;
+pub const EXTINGUISH_INTERVAL: u8 = 15;
+pub const EXTINGUISH_CHANCE_INV: u8 = 4;
+
+// Farms grow by one step once every interval until they're ripe
+// Farmers carry a few crops at a time to the hay or a stand, where each crop is sold for emeralds
+pub const FARM_GROWTH_INTERVAL: u8 = 30;
+pub const FARM_RIPE: u8 = 40;
+pub const FARMER_CAPACITY: u8 = 2;
+pub const EMERALDS_PER_CROP: u8 = 1;
+
+// Default illager income (see `IllagerIncome`)
+const PASSIVE_INCOME_INTERVAL: u16 = 120;
+const PASSIVE_INCOME: u8 = 1;
+const KILL_BOUNTY: u8 = 1;
+const GOLEM_BOUNTY: u8 = 3;
+const HOUSE_LOOT: u8 = 2;
+const CHURCH_LOOT: u8 = 5;
+const RAID_WAVE_INTERVAL: u32 = 60 * 60;
+const RAID_WAVE_BONUS: u8 = 10;
+
+// Emeralds of both players at the start of a match, for each preset of the setup screen
+pub const STARTING_EMERALDS: [[u8; 2]; 3] = [[100, 50], [200, 100], [250, 200]];
+
+// The illager AI thinks once every interval (in frames), and keeps that many emeralds for the reinforcements
+const AI_THINK_INTERVAL: u32 = 40;
+const AI_SAVINGS: u8 = REINFORCEMENTS;
+
+// Default victory conditions (see `VictoryConditions`)
+const SURVIVAL_TIME: u32 = 10 * 60 * 60;
+
+// Frames the illagers get to field someone new once they've all been killed
+const ELIMINATION_GRACE: u16 = 10 * 60;
+
 // Village stuff goes first since P1 is controlling the villagers
 const PRICES: [u8; 6] = [VILLAGER, FARMER, SMITH, VINDICATOR, PILLAGER, EVOKER];
 
+// Actions 3..=5 of the footer: villagers rally, put up barricades and ring the bell, illagers rally,
+// throw torches and call in reinforcements. Same order as PRICES
+const RALLY: u8 = 0;
+const BARRICADE: u8 = 4;
+const RING_BELL: u8 = 0;
+const TORCH: u8 = 6;
+const REINFORCEMENTS: u8 = 12;
+const ACTION_PRICES: [u8; 6] = [RALLY, BARRICADE, RING_BELL, RALLY, TORCH, REINFORCEMENTS];
+
+// Frames before an action can be used again
+const ACTION_COOLDOWNS: [u16; 6] = [30, 120, 20 * 60, 30, 5 * 60, 30 * 60];
+
+// Torches can only be thrown at things this close (in pixels) to an illager
+pub const TORCH_RANGE: u16 = 40;
+pub const REINFORCEMENT_COUNT: u8 = 2;
+
+// Units this close (in pixels) to the cursor get picked by the rally action, to give them orders
+pub const COMMAND_RADIUS: u16 = 20;
+
+// Frames villagers and farmers stay hidden in the houses and churches once the bell rang
+pub const ALARM_DURATION: u16 = 15 * 60;
+
 // Entities associated with illagers (vex included)
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Hash)]
 pub enum IllagerClan {
     Vindicator,
     Pillager,
+
+    // frames until the next spell
     Evoker(u8),
+
+    // frames left before vanishing
     Vex(u8),
 }
 
 // Unique state for every type of illager
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Hash)]
 pub enum IllagerState {
     Idle,
     Action,
 }
 
 // Unique state for golem
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Hash)]
 pub enum GolemState {
     Attack,
     Broken,
@@ -65,16 +197,22 @@ pub enum GolemState {
 }
 
 // Entities associated with villagers (golems included)
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Hash)]
 pub enum VillagerClan {
     Villager,
-    Farmer,
+
+    // crops being carried
+    Farmer(u8),
+
+    // work put into the next golem
     Smith(u8),
+
+    // repairs done while broken
     Golem(u8, GolemState),
 }
 
 // Currents state for the buildings
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Hash)]
 pub enum BuildingState {
     Solid,
     Burning,
@@ -82,17 +220,20 @@ pub enum BuildingState {
 }
 
 // Potential state of each cell
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Hash)]
 pub enum CellState {
     Empty,
 
     // 0, 1
     // 2, 3
-    House(BuildingState, u8),
+    // first u8 is the subcell index
+    // second u8 is the integrity, the same for all the sub-cells
+    House(BuildingState, u8, u8),
 
     // 0, 1
     // 2, 3
-    House2(BuildingState, u8),
+    // same as House
+    House2(BuildingState, u8, u8),
 
     // 0, 1
     // 2, 3
@@ -103,32 +244,43 @@ pub enum CellState {
     // 1
     Lamppost(u8),
 
-    Bell,
+    // illagers can break it, which stops it from ringing until a smith fixes it
+    Bell(BuildingState, u8),
 
     // 0, 1
     // 2, 3
-    Tree(u8),
+    // first u8 is the subcell index
+    // second u8 is the fuel left while it's burning (0 if it's not on fire)
+    Tree(u8, u8),
 
     // 0, 1
     // 2, 3
-    Stand(u8),
+    // same as Tree
+    Stand(u8, u8),
 
     // 0, 1
     // 2, 3
     // 4, 5
     // first u8 is the subcell index
-    // second u8 is the count down timer
+    // second u8 is the integrity (counting down to the KABOOM once it's burning)
     Church(BuildingState, u8, u8),
 
     // 0, 1
-    // todo: add timer for being farmed
-    Farm(u8),
+    // first u8 is the subcell index
+    // second u8 is the growth timer, the crops can be harvested once it reaches FARM_RIPE
+    // third u8 is the fuel left while it's burning, it doesn't grow in the meantime
+    Farm(u8, u8, u8),
 
     // 0, 1
-    Hay(u8),
+    // same as Tree
+    Hay(u8, u8),
+
+    // put up by the villagers to keep the illagers out
+    // u8 is the fuel left while it's burning (0 if it's not on fire)
+    Barricade(u8),
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 enum Direction {
     N,  // Up
@@ -141,15 +293,140 @@ enum Direction {
     SW, // Down-left
 }
 
+#[derive(Clone, Hash)]
 pub enum EntityType {
     IllagerClan(IllagerClan, IllagerState),
     VillagerClan(VillagerClan),
 }
 
+// Orders given by the players to a group of units
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Order {
+    // get there no matter what
+    Move(pathfinding::Goal),
+
+    // head there, fighting whoever we see on the way
+    Attack(pathfinding::Goal),
+}
+
+#[derive(Clone, Hash)]
 pub struct Entity {
     entity_type: EntityType,
     position_x: u16,
     position_y: u16,
+
+    // what the unit is walking toward, if anything
+    goal: Option<pathfinding::Goal>,
+
+    // pixel position of whoever we're going after (to fight or to repair), takes priority over the goal
+    target: Option<(u16, u16)>,
+
+    // what the player told us to do, beats everything else until we get there
+    order: Option<Order>,
+
+    // picked by the player for the next order
+    selected: bool,
+
+    // velocity in 1/16th of a pixel per frame, and the sub-pixel movement we haven't applied yet
+    velocity: (i8, i8),
+    remainder: (i8, i8),
+    facing: Direction,
+
+    // hit points left, and frames until we can attack again
+    health: u8,
+    cooldown: u8,
+}
+
+// Combat stats of a class
+pub struct ClassStats {
+    pub health: u8,
+    pub damage: u8,
+
+    // frames between two attacks
+    pub cooldown: u8,
+
+    // max distance (in pixels, on both axes) to whoever we're hitting
+    pub reach: u8,
+}
+
+impl Entity {
+    fn new(entity_type: EntityType, position_x: u16, position_y: u16) -> Self {
+        Self {
+            position_x,
+            position_y,
+            goal: None,
+            target: None,
+            order: None,
+            selected: false,
+            velocity: (0, 0),
+            remainder: (0, 0),
+            facing: Direction::E,
+            health: entity_type.stats().health,
+            cooldown: 0,
+            entity_type,
+        }
+    }
+
+    // Broken golems still stick around (they can be repaired), but they don't count as alive
+    fn alive(&self) -> bool {
+        self.health > 0
+    }
+
+    fn is_illager(&self) -> bool {
+        matches!(self.entity_type, EntityType::IllagerClan(..))
+    }
+
+    // Distance (in pixels, on both axes) to something
+    fn distance_to(&self, x: u16, y: u16) -> u16 {
+        x.abs_diff(self.position_x).max(y.abs_diff(self.position_y))
+    }
+
+    // Whether our target is close enough to be hit
+    fn engaged(&self) -> bool {
+        self.target.is_some_and(|(x, y)| self.distance_to(x, y) <= self.entity_type.stats().reach as u16)
+    }
+
+    fn face_toward(&mut self, x: u16, y: u16) {
+        let dx = (x as i16 - self.position_x as i16).signum() as i8;
+        let dy = (y as i16 - self.position_y as i16).signum() as i8;
+        if (dx, dy) != (0, 0) {
+            self.facing = direction_from_offset(dx, dy);
+        }
+    }
+}
+
+impl EntityType {
+    // Walking speed in 1/16th of a pixel per frame
+    fn speed(&self) -> i8 {
+        match self {
+            EntityType::VillagerClan(VillagerClan::Villager) => 6,
+            EntityType::VillagerClan(VillagerClan::Farmer(_)) => 6,
+            EntityType::VillagerClan(VillagerClan::Smith(_)) => 5,
+            EntityType::VillagerClan(VillagerClan::Golem(..)) => 4,
+            EntityType::IllagerClan(IllagerClan::Vindicator, _) => 7,
+            EntityType::IllagerClan(IllagerClan::Pillager, _) => 6,
+            EntityType::IllagerClan(IllagerClan::Evoker(_), _) => 5,
+            EntityType::IllagerClan(IllagerClan::Vex(_), _) => 10,
+        }
+    }
+
+    // Villagers, farmers and smiths can't fight back, they only have their legs
+    // Evokers don't fight either, they cast spells
+    // The reach of smiths and farmers is how close they have to be to repair a golem or harvest a farm
+    fn stats(&self) -> ClassStats {
+        let (health, damage, cooldown, reach) = match self {
+            EntityType::VillagerClan(VillagerClan::Villager) => (6, 0, 0, 0),
+            EntityType::VillagerClan(VillagerClan::Farmer(_)) => (6, 0, 0, 12),
+            EntityType::VillagerClan(VillagerClan::Smith(_)) => (8, 0, 0, 12),
+            EntityType::VillagerClan(VillagerClan::Golem(..)) => (30, 4, 50, 12),
+            EntityType::IllagerClan(IllagerClan::Vindicator, _) => (10, 3, 40, 12),
+            EntityType::IllagerClan(IllagerClan::Pillager, _) => (8, 2, 50, 40),
+            EntityType::IllagerClan(IllagerClan::Evoker(_), _) => (8, 0, 0, 0),
+            EntityType::IllagerClan(IllagerClan::Vex(_), _) => (3, 1, 30, 12),
+        };
+
+        ClassStats { health, damage, cooldown, reach }
+    }
 }
 
 /*
@@ -165,28 +442,61 @@ Sprites
 - Vex
 */
 
+// What the game is showing, and what the gamepads drive
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Screen {
+    Title,
+    Setup,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+// Entries of the title menu
+const TITLE_MENU: [&str; 4] = ["PLAY", "REPLAY", "SOUND", "PALETTE"];
+
+// Rows of the setup screen, the last one starts the match
+const SETUP_MENU: [&str; 5] = ["MODE", "SEED", "MAP", "EMERALDS", "START"];
+
 // p1: villager
 // p2: illager
-struct Game {
-    seed: u64,
-    emeralds: [u8; 2],
-    tick: u8,
-    cursors: [u16; 2],
-    old_gamepad: [u8; 2],
-    new_gamepad: [u8; 2],
-    cursor_timer: [u8; 2],
-    view_local_cameras: [(u8, u8); 2],
+pub struct Game {
+    sim: Simulation,
     current_player: u8,
-    button_held: [bool; 2],
-    action_possible: [bool; 2],
-    particles: Vec<Particle>,
-    entities: Vec<Entity>,
-
-    current_selected_class: [u8; 2],
-    goals: [u8; 4],
-    grid: Board,
+    screen: Screen,
+
+    // match that PLAY starts, edited on the setup screen
+    setup: MatchSetup,
+
+    // highlighted row of the title or setup menu
+    menu: u8,
+
+    // inputs of the current match, or the match we're playing back
+    recording: Recording,
+    replay: Option<Replay>,
+    frame: u32,
+
+    // inputs fed to the last step, to know who pressed something last
+    last_inputs: [u8; 2],
+
+    // raw gamepads of the last frame, to catch presses in the menus
+    last_pads: [u8; 2],
+
+    // buttons that stay masked in the match until they're released (the ones that started or resumed it)
+    held: u8,
+
+    // B1 / B2 pressed last frame, held back from the match in case the other one follows (to pause)
+    deferred: [u8; 2],
+
+    // frames played since the match was last written to disk
+    unsaved_frames: u32,
+
+    // only games started from the disk write back to it
+    save: Option<Save>,
+    settings: Settings,
 }
 
+#[derive(Clone, Hash)]
 struct Particle {
     x: u16,
     y: u16,
@@ -194,6 +504,19 @@ struct Particle {
     horizontal_velocity: i8,
 }
 
+// Crossbow bolt, positions are in 1/16th of a pixel so it can fly at any angle
+#[derive(Clone, Hash)]
+struct Projectile {
+    x: u16,
+    y: u16,
+    velocity: (i8, i8),
+    life: u8,
+    damage: u8,
+
+    // shot at a building, sets houses on fire
+    flaming: bool,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum Color {
     Lightest,
@@ -216,678 +539,418 @@ impl Color {
 }
 
 impl Game {
-    unsafe fn new() -> Self {
+    /// Creates a new game on the title screen, reading the save from disk
+    /// The seed on disk only moves once a match gets started
+    ///
+    /// # Safety
+    /// Must be called from the WASM-4 `start` callback (or on the host backend)
+    pub unsafe fn new() -> Self {
+        let save = Save::load();
+        let mut game = Self::with_seed(save.seed.wrapping_add(1));
+        game.settings = save.settings;
+        game.screen = Screen::Title;
+        game.save = Some(save);
+        game
+    }
+
+    /// Creates a new game straight into a local match from the given seed, without touching the disk
+    ///
+    /// # Safety
+    /// Must be called from the WASM-4 `start` callback (or on the host backend)
+    pub unsafe fn with_seed(seed: u64) -> Self {
         // lightest, lighter, darker, darkest
         *PALETTE = [0xeacfb2, 0xc69478, 0x8a5543, 0x441d1f];
 
-        // Read seed from disk and increment it, saving it again
-        let mut seed = 0u64;
-        diskr(
-            (&mut seed as *mut u64).cast::<u8>(),
-            std::mem::size_of::<u64>() as u32,
-        );
-        seed += 1;
-
-        diskw(
-            (&mut seed as *mut u64).cast::<u8>(),
-            std::mem::size_of::<u64>() as u32,
-        );
-
-        fastrand::seed(seed);
-        let grid = terrain::generate();
-
-        // the villager camera always starts at the middle of the map (village)
-        let (mid_x, mid_y) = (GRID_SIZE_X / 2, GRID_SIZE_Y / 2);
-        let village_cursor = grid_from_vec(mid_x, mid_y);
-
-
+        let setup = MatchSetup::new(seed);
         Self {
-            seed,
-            emeralds: [200, 100],
-            tick: 0,
-            cursors: [village_cursor, 0],
+            sim: Simulation::with_setup(&setup),
             current_player: 0,
-            button_held: [false, false],
-            action_possible: [false, false],
-            new_gamepad: [0; 2],
-            old_gamepad: [*GAMEPAD1, *GAMEPAD2],
-            cursor_timer: [0, 0],
-            current_selected_class: [0, 0],
-            particles: Vec::new(),
-            goals: [u8::MAX; 4],
-            grid,
-            entities: Vec::new(),
-            view_local_cameras: [(mid_x - GRID_LOCAL_SIZE_X / 2, mid_y - GRID_LOCAL_SIZE_Y / 2), (0, 0)],
+            screen: Screen::Playing,
+            setup,
+            menu: 0,
+            recording: Recording::with_setup(setup, save::REPLAY_CAPACITY),
+            replay: None,
+            frame: 0,
+            last_inputs: [0; 2],
+            last_pads: [0; 2],
+            held: 0,
+            deferred: [0; 2],
+            unsaved_frames: 0,
+            save: None,
+            settings: Settings::default(),
         }
     }
 
-    unsafe fn set_text_colors(text_color: Color, background_color: Color) {
-        *DRAW_COLORS = ((text_color.to_raw()) | (background_color.to_raw() << 4)) as u16;
-    }
+    /// Restarts the game as a playback of the match recorded on disk
+    /// Returns false if there is no recording to play
+    pub fn replay_last_match(&mut self) -> bool {
+        let Some(recording) = self.save.as_ref().and_then(|save| save.replay.clone()) else {
+            return false;
+        };
 
-    unsafe fn set_rect_colors(infill_color: Color, outline_color: Color) {
-        *DRAW_COLORS = ((infill_color.to_raw()) | (outline_color.to_raw() << 4)) as u16;
+        self.sim.reset(&recording.setup());
+        self.replay = Some(Replay::new(recording));
+        self.enter_match();
+        true
     }
 
-    unsafe fn run(&mut self) {
-        if (*NETPLAY >> 2) == 0 && MULTIPLAYER {
-            *DRAW_COLORS = 0b0100_0000_0000_0100;
-            text("Waiting for", 36, 20);
-            text("player 2", 46, 30);
-            text("and mohsin", 38, 40);
-            *DRAW_COLORS = 0b0100_0011_0010_0001;
-            sprites::draw_sprite(40, 8, 80, 10, 0, 180)
-        } else {
-            if MULTIPLAYER {
-                self.current_player = *NETPLAY & 0b11;
-            } else {
-                self.current_player = 0;
-            }
-
-            self.update();
-            
-            if (self.tick % FRAMES_PER_PARTICLE_TICK) == 0 {
-                self.update_particles();
-            }
-
-            self.action_possible();
-            self.fetch_input();
-            self.draw_background();
-            self.draw_sprites();
-            self.draw_entities();
-            self.draw_particles();
-            self.draw_footer();
-            self.draw_cursors();
-            self.draw_minimap();
-
-            if DEBUG_PALETTE {
-                self.debug_palette();
-            }
-
-            self.tick += 1;
-            self.tick %= 60;
-        }
+    pub fn simulation(&self) -> &Simulation {
+        &self.sim
     }
 
-    //
-    //
-    // "SUMMON" TYPE ACTIONS: 0..=2
-    // they summon:
-    //    index       |     0       |    1     |    2
-    // villager clan  |  villager   | farmer   | smith      
-    // illager clan   |  vindicator | pillager | evoker     
-    //
-    //
-    fn action_possible(&mut self) {
-        // === Villagers ===
-        // 1, 2, 3 & on house? true else false
-        // 4 on anything? true else false
-        // 5 on empty? true else false
-        // 6 ? true
-        // === Illagers ===
-        // 1, 2, 3 & on empty border ? true else false
-        // 4 on anything? true else false
-        // 5 on empty? true else false
-        // 6 ? true
-
-        fn at_border(index: u16) -> bool {
-            let (x, y) = vec_from_grid(index);
-            if x == 0 || x == GRID_SIZE_X - 1 || y == 0 || y == GRID_SIZE_Y - 1 {
-                true
-            } else {
-                false
-            }
-        }
-
-        // If we are controlling Villagers, we want to be able to summon villagers when doing the "Action" when we have a building selected
-        fn can_we_spawn_villagers(cell: &CellState, cursor: u16) -> bool {
-            matches!(*cell, CellState::House(_, _) | CellState::Church(_, _, _) | CellState::House2(_, _))
-        }
+    /// Starts a new match with the same options on the next map
+    ///
+    /// # Safety
+    /// Must be called from the WASM-4 callbacks (or on the host backend)
+    pub unsafe fn rematch(&mut self) {
+        self.setup.seed = self.setup.seed.wrapping_add(1);
+        self.start_match();
+    }
 
-        // If we are controlling Illagers, we want to be able to summon them at the border of the map, and on empty cells as well
-        fn can_we_spawn_illagers(cell: &CellState, cursor: u16) -> bool {
-            matches!(*cell, CellState::Empty) && at_border(cursor)
+    // Starts the match picked on the setup screen, the seed goes on disk so the next boot offers a new map
+    unsafe fn start_match(&mut self) {
+        if let Some(save) = self.save.as_mut() {
+            save.seed = self.setup.seed;
+            save.stats.matches = save.stats.matches.wrapping_add(1);
+            save.store();
         }
-        
-        for player_index in 0..2 {
-            // calculate the cursor position and cell of the current player
-            let cursor = self.cursors[player_index] as u16;
-            let cell = &self.grid[cursor as usize];
-
-            self.action_possible[player_index] = match self.current_selected_class[player_index] {
-                // actions 0..=2 are the "summon type actions"
-                // they summon:
-                //    index       |     0       |    1     |    2
-                // villager clan  |  villager   | farmer   | smith      
-                // illager clan   |  vindicator | pillager | evoker     
-                0..=2 => {
-                    // check if player has enough currency (them emmies)
-                    let player_has_enough_currency = self.emeralds[player_index] >= self.current_selected_class[player_index];
-
-                    // check if the player can do the specified action
-                    let player_can_do_thing = if player_index == 0 {
-                        can_we_spawn_villagers(cell, cursor)
-                    } else  {
-                        can_we_spawn_illagers(cell, cursor)
-                    };
-
-                    // check if we can do both...
-                    player_has_enough_currency && player_can_do_thing
-                }
 
-                3 | 5 => {
-                    true
-                }
+        self.sim.reset(&self.setup);
+        self.recording = Recording::with_setup(self.setup, save::REPLAY_CAPACITY);
+        self.replay = None;
+        self.enter_match();
+    }
 
-                4 => {
-                    if matches!(*cell, CellState::Empty) {
-                        true
-                    } else {
-                        false
-                    }
-                }
+    // Input state of a match that just started, whether it's played or replayed
+    fn enter_match(&mut self) {
+        self.screen = Screen::Playing;
+        self.last_inputs = [0; 2];
+        self.deferred = [0; 2];
 
-                _ => unreachable!()
-            };
-        }
+        // the button that picked START (or REPLAY) shouldn't summon anything
+        self.held = BUTTON_1 | BUTTON_2;
     }
 
-    // Fetch gamepad input. Also works in multiplayer. Only supports 2 players
-    // Also moves the appropriate selectors (and current player view if needed)
-    unsafe fn fetch_input(&mut self) {
-        // Moves the cursor, also moving the view local camera when it goes out of bounds
-        fn move_cursor(step_x: i8, step_y: i8, cursor: &mut u16, camera: &mut (i8, i8)) {
-            let mut x = (*cursor % GRID_SIZE_X as u16) as i8;
-            let mut y = (*cursor / GRID_SIZE_X as u16) as i8;
-
-            // Works ig
-            x += (x + step_x).clamp(0, GRID_SIZE_X as i8 - 1) - x;
-            y += (y + step_y).clamp(0, GRID_SIZE_Y as i8 - 1) - y;
-            let x = x as u8;
-            let y = y as u8;
-
-
-            fn uncontained_clamp(coord: &mut i8, val: i8, max: u8) {                
-                // local view min and max
-                let min = *coord;
-                let max = *coord + (max - 1) as i8;
-                
-                // max if x > max
-                // min if x < min
-                // val otherwise (to make subtraction 0)
-                let diff = if val < min {
-                    min
-                } else if val > max {
-                    max
-                } else {
-                    val
-                };
-
-                // move camera coordinate by difference
-                *coord += val - diff;
-            }
+    // Writes the recording and the time played to disk. Only done when the match ends or gets paused,
+    // as the whole disk gets encoded and written every time
+    unsafe fn save_match(&mut self) {
+        let Some(save) = self.save.as_mut().filter(|_| self.replay.is_none()) else {
+            return;
+        };
 
-            // Completely refactored camera movement code that will simply try to accodomate keeping the cursor in the 16x12 grid
-            // by taking cur diff to local edges
-            uncontained_clamp(&mut camera.0, x as i8, GRID_LOCAL_SIZE_X);
-            uncontained_clamp(&mut camera.1, y as i8, GRID_LOCAL_SIZE_Y);
+        save.stats.seconds_played = save.stats.seconds_played.wrapping_add(self.unsaved_frames / 60);
+        self.unsaved_frames %= 60;
+        save.replay = Some(self.recording.clone());
+        save.store();
+    }
 
-            *cursor = y as u16 * GRID_SIZE_X as u16 + x as u16;
+    // Leaves the match (or the replay) for the title screen, with the next map in the background
+    fn back_to_title(&mut self) {
+        if self.replay.take().is_none() {
+            self.setup.seed = self.setup.seed.wrapping_add(1);
         }
 
-        const GAMEPADS: [*const u8; 2] = [GAMEPAD1, GAMEPAD2];
-        for player_index in 0..2 {
-            let last = self.old_gamepad[player_index];
-            let current = *GAMEPADS[player_index];
-            let new = current & (last ^ current);
-            self.old_gamepad[player_index] = current;
-            self.new_gamepad[player_index] = new;
-
-            // Move cursor on grid
-            let grid_pos: &mut u16 = &mut self.cursors[player_index];
-            let camera = &mut self.view_local_cameras[player_index];
-            let tick: &mut u8 = &mut self.cursor_timer[player_index];
-            let cursor_tick_check: bool = *tick % CURSOR_MOVEMENT_SPEED_INV == 0;
-            *tick = tick.wrapping_add(1);
-
-            let mut step_left: i8 = -1;
-            let mut step_right: i8 = 1;
-            let mut step_down: i8 = 1;
-            let mut step_up: i8 = -1;
-
-            match &self.grid[*grid_pos as usize] {
-                CellState::House(_x, y) | CellState::House2(_x, y) => {
-                    if y % 2 == 0 {
-                        step_right *= 2
-                    } else {
-                        step_left *= 2
-                    };
-                    if y > &1 {
-                        step_up *= 2
-                    } else {
-                        step_down *= 2
-                    };
-                }
+        self.sim.reset(&self.setup);
+        self.screen = Screen::Title;
+        self.menu = 0;
+    }
 
-                CellState::Church(_x, y, _) => {
-                    if y % 2 == 0 {
-                        step_right *= 2
-                    } else {
-                        step_left *= 2
-                    };
-                    if y > &3 {
-                        step_up *= 3
-                    } else if y > &1 {
-                        step_up *= 2;
-                        step_down *= 2
-                    } else {
-                        step_down *= 3
-                    };
-                }
+    // Netplay matches wait for the second player to join (replays don't need them)
+    unsafe fn waiting_for_player_2(&self) -> bool {
+        self.setup.mode == Mode::Netplay && self.replay.is_none() && (*NETPLAY >> 2) == 0
+    }
 
-                CellState::BigRock(y) | CellState::Tree(y) | CellState::Stand(y) => {
-                    if y % 2 == 0 {
-                        step_right *= 2
-                    } else {
-                        step_left *= 2
-                    };
-                    if y > &1 {
-                        step_up *= 2
-                    } else {
-                        step_down *= 2
-                    };
-                }
+    // Both settings are kept on disk
+    unsafe fn store_settings(&mut self) {
+        if let Some(save) = self.save.as_mut() {
+            save.settings = self.settings;
+            save.store();
+        }
+    }
 
-                CellState::Lamppost(y) => {
-                    if y == &1 {
-                        step_up *= 2
-                    } else {
-                        step_down *= 2
-                    };
-                }
+    unsafe fn menu_sound(&self) {
+        if self.settings.sound {
+            play_me_some_tones______boy(Noise::Ting);
+        }
+    }
 
-                CellState::Farm(y) | CellState::Hay(y) => {
-                    if y % 2 == 0 {
-                        step_right *= 2
-                    } else {
-                        step_left *= 2
-                    };
-                }
+    // Up and down move through the rows (wrapping around), returns true if it moved
+    fn move_menu(&mut self, pressed: u8, rows: usize) -> bool {
+        let rows = rows as u8;
+        if pressed & BUTTON_UP != 0 {
+            self.menu = (self.menu + rows - 1) % rows;
+        } else if pressed & BUTTON_DOWN != 0 {
+            self.menu = (self.menu + 1) % rows;
+        } else {
+            return false;
+        }
+        true
+    }
 
-                _ => { }
-            }
+    unsafe fn update_title(&mut self, pressed: u8) {
+        if self.move_menu(pressed, TITLE_MENU.len()) {
+            self.menu_sound();
+        }
 
-            if cursor_tick_check && current != 0 {
-                let (x, y) = if current & BUTTON_UP != 0 {
-                    (0, step_up)
-                } else if current & BUTTON_DOWN != 0 {
-                    (0, step_down)
-                } else if current & BUTTON_LEFT != 0 {
-                    (step_left, 0)
-                } else if current & BUTTON_RIGHT != 0 {
-                    (step_right, 0)
-                } else {
-                    (0, 0)
-                };
+        if pressed & BUTTON_1 == 0 {
+            return;
+        }
 
-                let mut camera_temp = (camera.0 as i8, camera.1 as i8);
-                move_cursor(x, y, grid_pos, &mut camera_temp);
-                camera.0 = camera_temp.0 as u8;
-                camera.1 = camera_temp.1 as u8;
+        match self.menu {
+            0 => {
+                self.screen = Screen::Setup;
+                self.menu = 0;
             }
-            
-            if current == 0 {
-                *tick = 0;
+            1 => {
+                self.replay_last_match();
             }
-
-            let selected = &mut self.current_selected_class[player_index];
-
-            // Cycle current selected class
-            if new & BUTTON_2 != 0 {
-                *selected += 1;
-                *selected %= 6;
-                play_me_some_tones______boy(Noise::TungTungTungSahour);
+            2 => {
+                self.settings.sound = !self.settings.sound;
+                self.store_settings();
             }
-
-            // Place currently selected class
-            // Action possible permits this to happen,
-            // This is determined somewhere else (TBD)
-            if new & BUTTON_1 != 0 && self.action_possible[player_index] {
-                trace("player is doing action!");
-                let points: &mut u8 = &mut self.emeralds[player_index];
-
-                // make sure the cell is empty so we can place our shit there
-                // this needs to be redone as villagers have to be placed by selecting a house and will come out of the bottom
-                if *selected < 3 {
-                    // checked sub to make sure we don't cause a crash (also saves us from
-                    // manually comparing to check if we have enough points to spend)
-                    if let Some(new_points) =
-                        points.checked_sub(PRICES[*selected as usize + 3 * player_index])
-                    {
-                        // logic that handles setting new classes
-                        // this makes things so much easier lol nice
-                        // `cell` is the cell index position of a cell in which we can spawn illager / villagers
-                        let cell: Option<u16> = if player_index == 1 && matches!(self.grid[*grid_pos as usize], CellState::Empty) { 
-                            Some(*grid_pos)
-                        } else {
-                            // pick a plausible spawning position on the outline of the building
-                            trace("pick spawn location...");
-                            let random_position_building_outline: Option<u16> = match self.grid[*grid_pos as usize] {
-                                CellState::Church(BuildingState::Solid, j, 1..) => pick_random_location_building_outline(
-                                    &self.grid,
-                                    2,
-                                    3,
-                                    j, 
-                                    *grid_pos
-                                ),
-
-                                CellState::House(BuildingState::Solid, j) | CellState::House2(BuildingState::Solid, j) => pick_random_location_building_outline(
-                                    &self.grid,
-                                    2,
-                                    2,
-                                    j, 
-                                    *grid_pos
-                                ),
-
-                                _ => None
-                            };
-
-                            random_position_building_outline
-                        };
-
-                        // "player_index" is player index (where 0 is villager and 1 is illager)
-                        // "selected" is the selected class index (0..3)
-                        if let Some(plausible_cell_index) = cell { 
-                            let (x, y) = vec_from_grid(plausible_cell_index);
-
-                            *points = new_points;
-                            let entity_type = match (player_index, selected) {
-                                // villager clan classes
-                                (0, 0) => EntityType::VillagerClan(VillagerClan::Villager),
-                                (0, 1) => EntityType::VillagerClan(VillagerClan::Farmer),
-                                (0, 2) => EntityType::VillagerClan(VillagerClan::Smith(0)),
-
-                                // illager clan classes
-                                (1, 0) => EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle),
-                                (1, 1) => EntityType::IllagerClan(IllagerClan::Pillager, IllagerState::Idle),
-                                (1, 2) => EntityType::IllagerClan(IllagerClan::Evoker(0), IllagerState::Idle), 
-
-                                _ => unreachable!()
-                            };
-
-                            // spawn the new entity at the given cell position
-                            let global_pos_x = x as u16 * CELL_SIZE as u16;
-                            let global_pos_y = y as u16 * CELL_SIZE as u16;
-                            let entity = Entity {
-                                position_x: global_pos_x,
-                                position_y: global_pos_y,
-                                entity_type: entity_type,
-                            };
-
-                            self.entities.push(entity);
-                            
-                            play_me_some_tones______boy(Noise::Ting);
-                        } else {
-                            play_me_some_tones______boy(Noise::SixSeven);
-                        }
-                    }
-                } else {
-
-                }
+            _ => {
+                self.settings.debug_palette = !self.settings.debug_palette;
+                self.store_settings();
             }
-
-            self.button_held[player_index] = current & BUTTON_1 != 0;
         }
+        self.menu_sound();
     }
 
-    // Called every frame
-    unsafe fn update(&mut self) {
-        fn swap_cells(src: u16, dst: u16, grid: &mut [CellState]) {
-            let tmp = grid[src as usize];
-            grid[src as usize] = grid[dst as usize];
-            grid[dst as usize] = tmp;
+    unsafe fn update_setup(&mut self, pressed: u8) {
+        if pressed & BUTTON_2 != 0 {
+            self.screen = Screen::Title;
+            self.menu = 0;
+            return;
         }
 
-        fn try_move(index: u16, index_objective: u16, grid: &mut [CellState]) -> bool {
-            let dir = random_direction_with_diagonal();
-            if let Some(new_pos) = apply_direction(index, dir) {
-                if matches!(grid[new_pos as usize], CellState::Empty) {
-                    swap_cells(index, new_pos, grid);
-                }
-            }
-
-            false
-            /*
-            if matches!(grid[index_objective as usize], CellState::Empty) {
-                grid[index_objective as usize] = grid[index as usize];
-                grid[index as usize] = CellState::Empty;
-                true
-            } else {
-                false
-            }
-            */
-
-            //false
+        if self.move_menu(pressed, SETUP_MENU.len()) {
+            self.menu_sound();
         }
 
-        fn pathfind(index: u16, index_objective: u16, grid: &mut [CellState]) -> u16 {
-            let mut queue_list: Vec<u16> = Vec::new();
-            let mut current_score: u16 = 0;
-            let mut current = index;
-            
-            while current != index_objective {
-                current = match queue_list.pop() {
-                    None => break,
-                    Some(i) => i,
-                };
-                
-                for neighbour in get_neighbours(current).iter() {
-                    
-                }
-    
-                current_score += 1;
-    
-                break;
+        if self.menu as usize == SETUP_MENU.len() - 1 {
+            if pressed & BUTTON_1 != 0 {
+                self.start_match();
             }
-            
-            return 0;
+            return;
         }
 
-        let mut suspicious_grid: Box<[CellState; AREA]> = self.grid.clone();
-        let grid_ref: &mut [CellState; AREA] = &mut suspicious_grid;
-
-        for (index, state) in self.grid.iter().enumerate() {
-            match state {
-                CellState::Church(BuildingState::Burning, j, i @ 1..) => {
-                    if (self.tick % CHURCH_EXPLOSION_FREQUENCY_THING) == (CHURCH_EXPLOSION_FREQUENCY_THING / 2) {
-                        let new_i = i.saturating_sub(1);
-                        grid_ref[index] = CellState::Church(BuildingState::Burning, *j, new_i);
-
-                        if new_i > 0 {
-                            play_me_some_tones______boy(Noise::TungTungTungSahour);
-                        } else {
-                            // KABOOM TYPE TIMING!!!!!!!!!!
-                            play_me_some_tones______boy(Noise::KaboomNoise);
-
-                            let (dst_x, dst_y) = vec_from_grid(index as u16);
-                            let (mut dst_x, mut dst_y) = (dst_x as u16 * CELL_SIZE as u16, dst_y as u16 * CELL_SIZE as u16);
-
-                            dst_x += 5;
-                            dst_y += 5;
-
-                            for _ in 0..20 {
-                                // add randomness to start x position
-                                let rng_offset_x = fastrand::i16(-2..=2);
-                                let rng_offset_y = fastrand::i16(-2..=2);
-                                let rng_offset_life = fastrand::i8(-2..=7);
-
-                                // max particle count... otherwise... we doodoo....
-                                Self::summon_particle(&mut self.particles, Particle {
-                                    x: dst_x.saturating_add_signed(rng_offset_x),
-                                    y: dst_y.saturating_add_signed(rng_offset_y),
-                                    life: 5u8.saturating_add_signed(rng_offset_life),
-                                    horizontal_velocity: fastrand::i8(..),
-                                });
-                            }
-                        }
-                    }
-                },
-                CellState::Church(_, j, 0) => {
-                    grid_ref[index] = CellState::Church(BuildingState::Destroyed, *j, 0);
-                },
-                CellState::House(BuildingState::Burning, _) | CellState::House2(BuildingState::Burning, _) => {
-                    play_me_some_tones______boy(Noise::Burn);
-                }
+        let step: i8 = if pressed & BUTTON_LEFT != 0 {
+            -1
+        } else if pressed & BUTTON_RIGHT != 0 {
+            1
+        } else {
+            return;
+        };
 
+        // cycles through a list of options, wrapping around
+        fn cycle<T: Copy + PartialEq>(options: &[T], current: T, step: i8) -> T {
+            let index = options.iter().position(|option| *option == current).unwrap_or(0);
+            options[(index as i8 + step).rem_euclid(options.len() as i8) as usize]
+        }
 
-                _ => continue,
-            }
+        let setup = &mut self.setup;
+        match self.menu {
+            0 => setup.mode = cycle(&[Mode::Ai, Mode::Local, Mode::Netplay], setup.mode, step),
+            1 if step < 0 => setup.seed = setup.seed.saturating_sub(1),
+            1 => setup.seed = setup.seed.wrapping_add(1),
+            2 => setup.map_size = cycle(&[MapSize::Small, MapSize::Medium, MapSize::Large], setup.map_size, step),
+            _ => setup.emeralds = cycle(&STARTING_EMERALDS, setup.emeralds, step),
         }
 
-        self.grid = suspicious_grid;
+        // the map in the background follows the seed and size
+        self.sim.reset(&self.setup);
+        self.menu_sound();
+    }
 
-        for Entity { position_x, position_y, .. } in self.entities.iter_mut() {
-            /*
-                CellState::IllagerClan(id, _state) => match id {
-                    IllagerClan::Vindicator => {
-                        // check first for primary objectives (killing)
-                        try_move(index as u16, pathfind(index as u16, 0, grid_ref), grid_ref);
-                    }
-                    IllagerClan::Pillager => {
-                        // check first for primary objectives (shooting or pillaging)
-                        try_move(index as u16, pathfind(index as u16, 0, grid_ref), grid_ref);
-                    }
-                    IllagerClan::Evoker { .. } => {
-                        try_move(index as u16, pathfind(index as u16, 0, grid_ref), grid_ref);
-                    }
-                    IllagerClan::Vex { .. } => {
-                        // just wander around or kill lol
-                        try_move(index as u16, pathfind(index as u16, 0, grid_ref), grid_ref);
-                    }
-                },
+    // Steps the match with the inputs of this frame, `fresh` being the buttons that just got pressed
+    unsafe fn update_match(&mut self, fresh: [u8; 2]) {
+        // a replay that runs out before the match is over (truncated, or saved on pause) goes back to the title
+        let Some(inputs) = self.fetch_input(fresh) else {
+            self.back_to_title();
+            return;
+        };
 
-                CellState::VillagerClan(id) => match id {
-                    VillagerClan::Villager => {
-                        try_move(index as u16, pathfind(index as u16, 0, grid_ref), grid_ref);
-                    }
-                    VillagerClan::Farmer => {
-                        try_move(index as u16, pathfind(index as u16, 0, grid_ref), grid_ref);
-                    }
-                    VillagerClan::Smith { .. } => {
-                        try_move(index as u16, pathfind(index as u16, 0, grid_ref), grid_ref);
-                    }
-                    VillagerClan::Golem { .. } => {
-                        try_move(index as u16, pathfind(index as u16, 0, grid_ref), grid_ref);
-                    }
-                },
-            */
+        // on a shared screen, the view follows whoever pressed something last
+        let mode = if self.replay.is_some() { Mode::Local } else { self.setup.mode };
+        self.current_player = match mode {
+            Mode::Ai => 0,
+            Mode::Netplay => *NETPLAY & 0b11,
+            Mode::Local => (0..2u8)
+                .rev()
+                .find(|&player| inputs[player as usize] & !self.last_inputs[player as usize] != 0)
+                .unwrap_or(self.current_player),
+        };
+        self.last_inputs = inputs;
 
-            let new_position_x = position_x.saturating_add_signed(fastrand::i16(-4..=4)) as i16;
-            let new_position_y = position_y.saturating_add_signed(fastrand::i16(-4..=4)) as i16;
-        
-            let check_if_pos_is_empty = |new_position_x: i16, new_position_y: i16| {
-                if pixel_loc_in_grid_bounds(new_position_x, new_position_y) {
-                    let cell_position_x = (new_position_x as u16).div_ceil(CELL_SIZE as u16);
-                    let cell_position_y = (new_position_y as u16).div_ceil(CELL_SIZE as u16);
-                    matches!(self.grid[grid_from_vec(cell_position_x as u8, cell_position_y as u8) as usize], CellState::Empty)
-                } else {
-                    false
-                }
-            };
+        self.sim.step(inputs);
+        for noise in self.sim.sounds().iter().filter(|_| self.settings.sound) {
+            play_me_some_tones______boy(*noise);
+        }
 
-            let corner_1 = check_if_pos_is_empty(new_position_x, new_position_y);
-            let corner_2 = check_if_pos_is_empty(new_position_x - 8, new_position_y);
-            let corner_3 = check_if_pos_is_empty(new_position_x, new_position_y - 8);
-            let corner_4 = check_if_pos_is_empty(new_position_x - 8, new_position_y - 8);
+        if self.sim.outcome().is_some() {
+            self.screen = Screen::GameOver;
+            self.save_match();
+        }
+    }
+
+    unsafe fn set_text_colors(text_color: Color, background_color: Color) {
+        *DRAW_COLORS = ((text_color.to_raw()) | (background_color.to_raw() << 4)) as u16;
+    }
 
+    unsafe fn set_rect_colors(infill_color: Color, outline_color: Color) {
+        *DRAW_COLORS = ((infill_color.to_raw()) | (outline_color.to_raw() << 4)) as u16;
+    }
 
-            if corner_1 && corner_2 && corner_3 && corner_4 && (self.tick % 4) == 0 {
-                *position_x = new_position_x as u16;
-                *position_y = new_position_y as u16;
+    /// Runs a single frame (update, input and rendering)
+    ///
+    /// # Safety
+    /// Must be called from the WASM-4 `update` callback (or on the host backend)
+    pub unsafe fn run(&mut self) {
+        // menus react to presses of either gamepad, pausing to both buttons pressed together
+        let pads = [*GAMEPAD1, *GAMEPAD2];
+        let fresh = [pads[0] & !self.last_pads[0], pads[1] & !self.last_pads[1]];
+        let pressed = fresh[0] | fresh[1];
+        let both = BUTTON_1 | BUTTON_2;
+        let pause = (0..2).any(|player| pads[player] & both == both && self.last_pads[player] & both != both);
+        self.last_pads = pads;
+        self.frame = self.frame.wrapping_add(1);
+
+        if self.screen == Screen::Playing && self.waiting_for_player_2() {
+            if pressed & BUTTON_2 != 0 {
+                self.screen = Screen::Setup;
             }
+
+            *DRAW_COLORS = 0b0100_0000_0000_0100;
+            text("Waiting for", 36, 20);
+            text("player 2", 46, 30);
+            text("and mohsin", 38, 40);
+            *DRAW_COLORS = 0b0100_0011_0010_0001;
+            sprites::draw_sprite(40, 8, 80, 10, 0, 180);
+            return;
         }
-    }
 
-    // Do particle effect shit
-    unsafe fn update_particles(&mut self) {
-        for (index, state) in self.grid.iter().enumerate() {
-            match state {
-                // make sure we only look at the TOP LEFT sub-cell of the house
-                // everything will now use that local reference frame
-                CellState::House(BuildingState::Burning, 0) | CellState::House2(BuildingState::Burning, 0) => {
-                    let (dst_x, dst_y) = vec_from_grid(index as u16);
-                    let (mut dst_x, mut dst_y) = (dst_x as u16 * CELL_SIZE as u16, dst_y as u16 * CELL_SIZE as u16);
-
-                    // make sure the smoke starts at the door
-                    dst_x += 8;
-                    dst_y += 14;
-
-                    for _ in 0..3 {
-                        // add randomness to start x position
-                        let rng_offset_x = fastrand::u16(0..4);
-                        let rng_offset_life = fastrand::u8(0..4);
-                        Self::summon_particle(&mut self.particles, Particle {
-                            x: dst_x + rng_offset_x,
-                            y: dst_y,
-                            life: 16u8 + rng_offset_life,
-                            horizontal_velocity: 0,
-                        });
+        match self.screen {
+            Screen::Title => self.update_title(pressed),
+            Screen::Setup => self.update_setup(pressed),
+            Screen::Playing if pause => {
+                self.screen = Screen::Paused;
+                self.deferred = [0; 2];
+                self.save_match();
+            }
+            Screen::Paused if pause => {
+                self.screen = Screen::Playing;
+                self.held = both;
+                self.deferred = [0; 2];
+            }
+            Screen::Playing => self.update_match(fresh),
+
+            // B2 on its own leaves for the title, once it's let go so it can't be the first half of the combo
+            Screen::Paused => {
+                let mut quit = false;
+                for player in 0..2 {
+                    if pads[player] & BUTTON_1 != 0 {
+                        self.deferred[player] = 0;
+                    } else if fresh[player] & BUTTON_2 != 0 {
+                        self.deferred[player] = BUTTON_2;
+                    } else if self.deferred[player] != 0 && pads[player] & BUTTON_2 == 0 {
+                        quit = true;
                     }
                 }
 
-                _ => continue,
+                if quit {
+                    self.save_match();
+                    self.back_to_title();
+                }
             }
-        }
 
-        for Particle { x, y, life, horizontal_velocity } in self.particles.iter_mut() {
-            *y = y.wrapping_sub(1);
+            // either player can ask for a rematch once the results are up (not while watching a replay though)
+            Screen::GameOver => {
+                if pressed & BUTTON_1 != 0 && self.replay.is_none() {
+                    self.rematch();
+                } else if pressed & BUTTON_2 != 0 {
+                    self.save_match();
+                    self.back_to_title();
+                }
+            }
+        }
 
-            // randomize x spread
-            if fastrand::bool() {
-                *x = x.wrapping_add_signed(fastrand::i16(-1..=1));
+        self.draw_background();
+        self.draw_sprites();
+        match self.screen {
+            // the map of the next match sits behind the menus
+            Screen::Title | Screen::Setup => {
+                self.draw_footer();
+                self.draw_minimap();
+                if self.screen == Screen::Title {
+                    self.draw_title();
+                } else {
+                    self.draw_setup();
+                }
+            }
+            _ => {
+                self.draw_entities();
+                self.draw_particles();
+                self.draw_footer();
+                self.draw_cursors();
+                self.draw_minimap();
             }
+        }
 
-            *life = life.saturating_sub(1);
+        if let Some(outcome) = self.sim.outcome().filter(|_| self.screen == Screen::GameOver) {
+            self.draw_results(outcome);
+        }
 
-            if horizontal_velocity.unsigned_abs() < 64 && fastrand::bool() {
-                *x = x.saturating_add_signed((*horizontal_velocity / 4) as i16);
-            } else {
-                *x = x.saturating_add_signed((*horizontal_velocity / 32) as i16);
-            }
+        if self.screen == Screen::Paused {
+            Self::set_rect_colors(Color::Lightest, Color::Darkest);
+            rect(48, 50, 64, 30);
+            Self::set_text_colors(Color::Darkest, Color::Lightest);
+            text("PAUSED", 56, 56);
+            Self::set_text_colors(Color::Darker, Color::Lightest);
+            text(b"\x81 MENU", 56, 68);
+        }
 
-            *horizontal_velocity /= 2;
+        if self.replay.is_some() {
+            Self::set_text_colors(Color::Lightest, Color::Darkest);
+            text("REPLAY", 1, 1);
         }
 
-        self.particles.retain(|Particle { x, y, life, .. }| *life > 0 && *x < (CELL_SIZE as u16 * GRID_SIZE_X as u16) && *y < (CELL_SIZE as u16 * GRID_SIZE_Y as u16));
-        self.particles.sort_unstable_by_key(|particle| particle.life);
-        self.particles.shrink_to_fit();
+        if self.settings.debug_palette {
+            self.debug_palette();
+        }
+        *DRAW_COLORS = 0b0100_0011_0010_0001;
     }
 
-    // Custom function to summon particle
-    fn summon_particle(particles: &mut Vec<Particle>, particle: Particle) {
-        if particles.len() > MAX_PARTICLE_COUNT {
-            // holy fuck... this is so bad.... lol lmao
-            particles.sort_unstable_by_key(|particle| particle.life);
-            particles.remove(particles.len() - 1);
-            particles.push(particle);
-            particles.sort_unstable_by_key(|particle| particle.life);
-        } else {
-            particles.push(particle);
+    // Fetch gamepad input of both players. Also works in multiplayer
+    // When replaying, the inputs come from the recording instead (None once it's over)
+    unsafe fn fetch_input(&mut self, fresh: [u8; 2]) -> Option<[u8; 2]> {
+        if let Some(replay) = &mut self.replay {
+            return replay.next_inputs();
         }
-        /*
-        // max particle count... otherwise... we doodoo....
-        if particles.len() < MAX_PARTICLE_COUNT {
+
+        // buttons still held from the menus are ignored until they're let go
+        let pads = [*GAMEPAD1, *GAMEPAD2];
+        self.held &= pads[0] | pads[1];
+
+        // a new press of B1 or B2 reaches the match a frame late, so it never sees (or records) half of
+        // the pause combo. Held back presses go through even if the button got released already
+        let both = BUTTON_1 | BUTTON_2;
+        let mut inputs = [0; 2];
+        for player in 0..2 {
+            inputs[player] = (pads[player] | self.deferred[player]) & !(fresh[player] & both) & !self.held;
+            self.deferred[player] = fresh[player] & both;
         }
-        */
+        self.recording.record(inputs);
+        self.unsaved_frames += 1;
+        Some(inputs)
     }
-    
+
     // Render particle effect shit
     unsafe fn draw_particles(&self) {
-        let (offset_x, offset_y) = self.view_local_cameras[self.current_player as usize];
+        let (offset_x, offset_y) = self.sim.view_local_cameras[self.current_player as usize];
         let range_pixel_x = ((offset_x as u16 * CELL_SIZE as u16))..(((offset_x + GRID_LOCAL_SIZE_X) as u16 * CELL_SIZE as u16));
         let range_pixel_y = ((offset_y as u16 * CELL_SIZE as u16))..(((offset_y + GRID_LOCAL_SIZE_Y) as u16 * CELL_SIZE as u16));
 
-        for Particle { x, y, .. } in self.particles.iter() {
+        for Particle { x, y, .. } in self.sim.particles.iter() {
             if range_pixel_x.contains(x) && range_pixel_y.contains(y) {
                 Self::set_rect_colors(Color::Lightest, Color::Lightest);
                 rect((x - range_pixel_x.start) as i32, (y - range_pixel_y.start) as i32, 2, 3);
@@ -897,9 +960,9 @@ impl Game {
 
     // Draw a footer containing points, classes to summon, and current selected cell
     unsafe fn draw_footer(&mut self) {
-        let class = self.current_selected_class[self.current_player as usize];
+        let class = self.sim.current_selected_class[self.current_player as usize];
         let self_clan_entity_count: u16 = {
-            self.entities.iter().map(|entity| {
+            self.sim.entities.iter().map(|entity| {
                 if self.current_player == 0 {
                     matches!(entity.entity_type, EntityType::VillagerClan(_))
                 } else {
@@ -917,7 +980,7 @@ impl Game {
         Self::set_text_colors(Color::Darkest, Color::Lightest);
         let mut buffer = itoa::Buffer::new();
         text(
-            buffer.format(self.emeralds[self.current_player as usize]),
+            buffer.format(self.sim.emeralds[self.current_player as usize]),
             71,
             135,
         );
@@ -926,7 +989,7 @@ impl Game {
         text(buffer.format(self_clan_entity_count), 71, 144);
 
         *DRAW_COLORS = 0b0100_0011_0010_0001;
-        let button = self.button_held[self.current_player as usize];
+        let button = self.sim.button_held[self.current_player as usize];
 
         // Draw class portraits - width 17, height 27
         for x in 0..3 {
@@ -955,16 +1018,157 @@ impl Game {
                 9 - offset as u32,
                 51,
                 147 + 9 * x as u32,
-            )
+            );
+
+            // shrinking bar under the actions that are cooling down
+            let cooldown = self.sim.action_cooldowns[self.current_player as usize][x as usize] as i32;
+            if cooldown > 0 {
+                let total = ACTION_COOLDOWNS[x as usize + 3 * self.current_player as usize] as i32;
+                Self::set_rect_colors(Color::Darkest, Color::Darkest);
+                rect(61 + 11 * x, 133, (9 * cooldown / total).max(1) as u32, 1);
+                *DRAW_COLORS = 0b0100_0011_0010_0001;
+            }
         }
 
         // Draw villager and emerald symbols (text above)
         sprites::draw_sprite(62, 135, 6, 7, 51, 123);
         sprites::draw_sprite(62, 144, 6, 7, 51, 130);
 
+        // Seconds until the next raid wave (and its bonus) for the illagers
+        if let Some(frames) = self.sim.next_raid_wave().filter(|_| self.current_player == 1) {
+            Self::set_text_colors(Color::Darkest, Color::Lightest);
+            text("RAID", 96, 135);
+            text(buffer.format(frames.div_ceil(60)), 96, 144);
+        }
+
+        // and the seconds the villagers have to hold on for
+        if let Some(frames) = self.sim.survival_left().filter(|_| self.current_player == 0) {
+            Self::set_text_colors(Color::Darkest, Color::Lightest);
+            text("HOLD", 96, 135);
+            text(buffer.format(frames.div_ceil(60)), 96, 144);
+        }
+
         // Draw log? todo
     }
 
+    // Who won, why, and a few numbers about the match on top of the frozen board
+    unsafe fn draw_results(&self, outcome: Outcome) {
+        Self::set_rect_colors(Color::Lightest, Color::Darkest);
+        rect(8, 12, 144, 100);
+
+        let title = if outcome.winner() == self.current_player as usize { "VICTORY" } else { "DEFEAT" };
+        let reason = match outcome {
+            Outcome::ChurchesDestroyed => "CHURCHES FELL",
+            Outcome::HousesDestroyed => "HOUSES BURNED",
+            Outcome::Survived => "VILLAGE HELD",
+            Outcome::IllagersEliminated => "RAID REPELLED",
+        };
+
+        // centered, the font is 8 pixels wide
+        Self::set_text_colors(Color::Darkest, Color::Lightest);
+        text(title, 80 - title.len() as i32 * 4, 18);
+        Self::set_text_colors(Color::Darker, Color::Lightest);
+        text(reason, 80 - reason.len() as i32 * 4, 28);
+
+        // villagers then illagers, the burned buildings go under the villagers
+        let MatchStats { spawned, lost, emeralds_earned, buildings_burned } = *self.sim.stats();
+        let rows = [("SPAWNED", spawned), ("LOST", lost), ("EARNED", emeralds_earned), ("BURNED", [buildings_burned, u16::MAX])];
+        let mut buffer = itoa::Buffer::new();
+        Self::set_text_colors(Color::Darkest, Color::Lightest);
+        text("VIL", 80, 42);
+        text("ILL", 116, 42);
+        for (i, (label, values)) in rows.into_iter().enumerate() {
+            let y = 54 + i as i32 * 10;
+            text(label, 14, y);
+            for (column, value) in values.into_iter().enumerate().filter(|(_, value)| *value != u16::MAX) {
+                text(buffer.format(value), 80 + column as i32 * 36, y);
+            }
+        }
+
+        if self.replay.is_none() {
+            text(b"\x80 REMATCH  \x81 MENU", 12, 98);
+        } else {
+            text(b"\x81 MENU", 48, 98);
+        }
+        *DRAW_COLORS = 0b0100_0011_0010_0001;
+    }
+
+    // Box the menus are drawn in, same as the results
+    unsafe fn draw_menu_box(&self) {
+        Self::set_rect_colors(Color::Lightest, Color::Darkest);
+        rect(8, 12, 144, 100);
+    }
+
+    // Arrow in front of the highlighted row
+    unsafe fn draw_menu_row(&self, row: u8, label: &str, x: i32, y: i32) {
+        let color = if self.menu == row { Color::Darkest } else { Color::Darker };
+        Self::set_text_colors(color, Color::Lightest);
+        if self.menu == row {
+            text(">", x - 10, y);
+        }
+        text(label, x, y);
+    }
+
+    unsafe fn draw_title(&self) {
+        self.draw_menu_box();
+        Self::set_text_colors(Color::Darkest, Color::Lightest);
+        text("VILLAGERS", 44, 20);
+        text("VS ILLAGERS", 36, 30);
+
+        let on_off = |on: bool| if on { "ON" } else { "OFF" };
+        let values = ["", "", on_off(self.settings.sound), on_off(self.settings.debug_palette)];
+        for (row, (label, value)) in TITLE_MENU.into_iter().zip(values).enumerate() {
+            let y = 50 + row as i32 * 12;
+            self.draw_menu_row(row as u8, label, 40, y);
+            text(value, 112, y);
+        }
+
+        // nothing to watch on a fresh disk
+        if self.save.as_ref().is_none_or(|save| save.replay.is_none()) {
+            Self::set_rect_colors(Color::Darker, Color::Darker);
+            rect(40, 66, 48, 1);
+        }
+        *DRAW_COLORS = 0b0100_0011_0010_0001;
+    }
+
+    unsafe fn draw_setup(&self) {
+        self.draw_menu_box();
+        Self::set_text_colors(Color::Darkest, Color::Lightest);
+        text("MATCH SETUP", 36, 18);
+
+        let MatchSetup { mode, seed, map_size, emeralds } = self.setup;
+        let mut buffer = itoa::Buffer::new();
+        let mut emeralds_buffer = itoa::Buffer::new();
+        let emeralds = [buffer.format(emeralds[0]), "/", emeralds_buffer.format(emeralds[1])].concat();
+        let mut seed_buffer = itoa::Buffer::new();
+        let values = [
+            match mode {
+                Mode::Ai => "VS AI",
+                Mode::Local => "LOCAL",
+                Mode::Netplay => "NETPLAY",
+            },
+            seed_buffer.format(seed),
+            match map_size {
+                MapSize::Small => "SMALL",
+                MapSize::Medium => "MEDIUM",
+                MapSize::Large => "LARGE",
+            },
+            &emeralds,
+        ];
+
+        for (row, label) in SETUP_MENU.into_iter().enumerate() {
+            let y = 34 + row as i32 * 12;
+            self.draw_menu_row(row as u8, label, 20, y);
+            if let Some(value) = values.get(row) {
+                text(value, 92, y);
+            }
+        }
+
+        Self::set_text_colors(Color::Darker, Color::Lightest);
+        text(b"\x81 BACK", 48, 98);
+        *DRAW_COLORS = 0b0100_0011_0010_0001;
+    }
+
     // Draw the background color
     fn draw_background(&self) {
         unsafe {
@@ -978,10 +1182,10 @@ impl Game {
     fn draw_background_grass(&self, base: (u8, u8), offset: (u8, u8), dst: (i32, i32)) {
         let (x, flip, variant) = {
             let a = ((base.0 + offset.0) as u64
-                + (self.seed.wrapping_mul(0x9E3779B97F4A7C15) % 1684))
+                + (self.sim.seed.wrapping_mul(0x9E3779B97F4A7C15) % 1684))
                 .wrapping_mul(0x4a9b41c68d);
             let b = ((base.1 + offset.1) as u64
-                + (self.seed.wrapping_mul(0x6c7967656e657261) % 6475))
+                + (self.sim.seed.wrapping_mul(0x6c7967656e657261) % 6475))
                 .wrapping_mul(0x94ba7c6d9b);
             let t = 0xffffffffu32 as f32;
             let hash = ((((a ^ b) as f32) / t) * 10.0) as u32;
@@ -1010,41 +1214,35 @@ impl Game {
         *DRAW_COLORS = 0b0100_0011_0010_0001;
 
         // Used for burning buildings
-        
+        let burning_x_offset = ((self.sim.tick % BURN_INTERVAL) < BURN_INTERVAL / 2) as u32 * 20;
 
         for base_x in 0..GRID_LOCAL_SIZE_X {
             for base_y in 0..GRID_LOCAL_SIZE_Y {
-                let (offset_x, offset_y) = self.view_local_cameras[self.current_player as usize];
+                let (offset_x, offset_y) = self.sim.view_local_cameras[self.current_player as usize];
                 let state =
-                    &self.grid[grid_from_vec(base_x + offset_x, base_y + offset_y) as usize];
+                    &self.sim.grid[grid_from_vec(base_x + offset_x, base_y + offset_y) as usize];
                 let dst_x = (base_x * CELL_SIZE) as i32;
                 let dst_y = (base_y * CELL_SIZE) as i32;
 
                 self.draw_background_grass((base_x, base_y), (offset_x, offset_y), (dst_x, dst_y));
 
                 match state {
-                    CellState::House(state, i) => {
-                        let src_x = 0;
-                        /*
+                    CellState::House(state, i, _) => {
                         let src_x = match state {
                             BuildingState::Solid => 0,
                             BuildingState::Burning => 20 + burning_x_offset,
                             BuildingState::Destroyed => 60,
                         };
-                        */
 
                         sprites::draw_multi_grid_sprite(*i, 2, src_x, 20, dst_x, dst_y);
                     }
 
-                    CellState::House2(state, i) => {
-                        let src_x = 0;
-                        /*
+                    CellState::House2(state, i, _) => {
                         let src_x = match state {
                             BuildingState::Solid => 0,
                             BuildingState::Burning => 20 + burning_x_offset,
                             BuildingState::Destroyed => 60,
                         };
-                        */
 
                         sprites::draw_multi_grid_sprite(*i, 2, src_x, 60, dst_x, dst_y);
                     }
@@ -1056,20 +1254,43 @@ impl Game {
                     CellState::Lamppost(i) => {
                         sprites::draw_multi_grid_sprite(*i, 1, 20, 40, dst_x, dst_y)
                     }
-                    CellState::Bell => sprites::draw_grid_sprite(30, 50, dst_x, dst_y),
-                    CellState::Tree(i) => sprites::draw_multi_grid_sprite(*i, 2, 40, 40, dst_x, dst_y),
-                    CellState::Stand(i) => sprites::draw_multi_grid_sprite(*i, 2, 60, 40, dst_x, dst_y),
+                    CellState::Bell(state, _) => {
+                        sprites::draw_grid_sprite(30, 50, dst_x, dst_y);
+
+                        // no sprite for a broken one either, so it gets a crack
+                        if matches!(state, BuildingState::Destroyed) {
+                            Self::set_rect_colors(Color::Darkest, Color::Darkest);
+                            line(dst_x + 3, dst_y + 2, dst_x + 5, dst_y + 5);
+                            line(dst_x + 5, dst_y + 5, dst_x + 4, dst_y + 8);
+                            *DRAW_COLORS = 0b0100_0011_0010_0001;
+                        }
+                    }
+                    CellState::Tree(i, _) => sprites::draw_multi_grid_sprite(*i, 2, 40, 40, dst_x, dst_y),
+                    CellState::Stand(i, _) => sprites::draw_multi_grid_sprite(*i, 2, 60, 40, dst_x, dst_y),
                     CellState::Church(state, i, _) => {
                         let src_x = match state {
                             BuildingState::Solid => 0,
-                            BuildingState::Burning => 20 + ((self.tick % CHURCH_EXPLOSION_FREQUENCY_THING) < CHURCH_EXPLOSION_FREQUENCY_THING / 2) as u32 * 20,
+                            BuildingState::Burning => 20 + burning_x_offset,
                             BuildingState::Destroyed => 60,
                         };
 
                         sprites::draw_multi_grid_sprite(*i, 2, src_x, 80, dst_x, dst_y);
                     }
-                    CellState::Farm(i) => sprites::draw_multi_grid_sprite(*i, 2, 0, 110, dst_x, dst_y),
-                    CellState::Hay(i) => sprites::draw_multi_grid_sprite(*i, 2, 40, 110, dst_x, dst_y),
+                    CellState::Farm(i, growth, _) => {
+                        let src_x = if *growth >= FARM_RIPE { 0 } else { 20 };
+                        sprites::draw_multi_grid_sprite(*i, 2, src_x, 110, dst_x, dst_y)
+                    }
+                    CellState::Hay(i, _) => sprites::draw_multi_grid_sprite(*i, 2, 40, 110, dst_x, dst_y),
+                    CellState::Barricade(fire) => {
+                        // no sprite for it, so two posts and a couple of planks it is
+                        let color = if *fire > 0 && burning_x_offset > 0 { Color::Darker } else { Color::Lighter };
+                        Self::set_rect_colors(color, Color::Darkest);
+                        rect(dst_x + 1, dst_y + 1, 3, 9);
+                        rect(dst_x + 6, dst_y + 1, 3, 9);
+                        rect(dst_x, dst_y + 2, 10, 3);
+                        rect(dst_x, dst_y + 6, 10, 3);
+                        *DRAW_COLORS = 0b0100_0011_0010_0001;
+                    }
                     _ => continue,
                 }
             }
@@ -1077,45 +1298,73 @@ impl Game {
     }
 
     // Draw entities
-    unsafe fn draw_entities(&mut self) {
-        let (offset_x, offset_y) = self.view_local_cameras[self.current_player as usize];
+    unsafe fn draw_entities(&self) {
+        let (offset_x, offset_y) = self.sim.view_local_cameras[self.current_player as usize];
         let range_pixel_x = ((offset_x as u16 * CELL_SIZE as u16))..(((offset_x + GRID_LOCAL_SIZE_X) as u16 * CELL_SIZE as u16));
         let range_pixel_y = ((offset_y as u16 * CELL_SIZE as u16))..(((offset_y + GRID_LOCAL_SIZE_Y) as u16 * CELL_SIZE as u16));
 
-        self.entities.sort_unstable_by_key(|entity| entity.position_y);
+        // back to front, sorting our own list since the order of the entities is part of the simulation
+        let mut entities: Vec<&Entity> = self.sim.entities.iter().collect();
+        entities.sort_by_key(|entity| entity.position_y);
 
-        for Entity { position_x, position_y, entity_type } in self.entities.iter() {
+        for Entity { position_x, position_y, entity_type, facing, selected, .. } in entities {
             let dst_x = *position_x as i32 - range_pixel_x.start as i32;
             let dst_y = *position_y as i32 - range_pixel_y.start as i32;
 
+            // units waiting for an order stand on a little line
+            if *selected {
+                Self::set_rect_colors(Color::Lightest, Color::Lightest);
+                line(dst_x + 1, dst_y + 10, dst_x + 8, dst_y + 10);
+                *DRAW_COLORS = 0b0100_0011_0010_0001;
+            }
+
+            // sprites are facing right
+            let flip = matches!(facing, Direction::W | Direction::NW | Direction::SW);
             match entity_type {
-                EntityType::IllagerClan(_type, state) => sprites::draw_illager_entity(dst_x, dst_y, _type, state),
-                EntityType::VillagerClan(_type) => sprites::draw_villager_entity(dst_x, dst_y, _type),
+                EntityType::IllagerClan(_type, state) => sprites::draw_illager_entity(dst_x, dst_y, _type, state, flip),
+                EntityType::VillagerClan(_type) => sprites::draw_villager_entity(dst_x, dst_y, _type, flip),
             }
         }
+
+        // bolts are a short line trailing behind their tip
+        Self::set_rect_colors(Color::Darkest, Color::Darkest);
+        for Projectile { x, y, velocity, .. } in self.sim.projectiles.iter() {
+            let (x, y) = ((x / 16) as i32 - range_pixel_x.start as i32, (y / 16) as i32 - range_pixel_y.start as i32);
+            line(x, y, x - velocity.0 as i32 / 8, y - velocity.1 as i32 / 8);
+        }
+
+        // our rally flag, a pole with a little banner on top
+        if let Some(flag) = self.sim.rally[self.current_player as usize] {
+            let (x, y) = vec_from_grid(flag);
+            let dst_x = (x as i32 - offset_x as i32) * CELL_SIZE as i32;
+            let dst_y = (y as i32 - offset_y as i32) * CELL_SIZE as i32;
+            line(dst_x + 3, dst_y + 1, dst_x + 3, dst_y + 9);
+            Self::set_rect_colors(Color::Lightest, Color::Darkest);
+            rect(dst_x + 4, dst_y + 1, 5, 4);
+        }
     }
 
     
     // Draw the player cursors. Different colors assigned to each team
     unsafe fn draw_cursors(&self) {
         let index = self.current_player as usize;
-        *DRAW_COLORS = if self.action_possible[index] { 0b0000_0000_0001_0000 } else { 0b0000_0000_0100_0000 };
-        let (posx, posy) = vec_from_grid(self.cursors[index]);
-        let posx = posx.saturating_sub(self.view_local_cameras[index].0) as i32;
-        let posy = posy.saturating_sub(self.view_local_cameras[index].1) as i32;
-        let offset: i32 = if self.tick > 30 && self.action_possible[index] { 1 } else { 0 };
+        *DRAW_COLORS = if self.sim.action_possible[index] { 0b0000_0000_0001_0000 } else { 0b0000_0000_0100_0000 };
+        let (posx, posy) = vec_from_grid(self.sim.cursors[index]);
+        let posx = posx.saturating_sub(self.sim.view_local_cameras[index].0) as i32;
+        let posy = posy.saturating_sub(self.sim.view_local_cameras[index].1) as i32;
+        let offset: i32 = if self.sim.tick > 30 && self.sim.action_possible[index] { 1 } else { 0 };
         let mut offset_x: i32 = 0;
         let mut offset_y: i32 = 0;
         let mut offset_x2: i32 = 0;
         let mut offset_y2: i32 = 0;
 
         // this will need to be around a "selected element", i.e. a building. Rendering can be separated from logic, this means that cursor will do something idk
-        match self.grid[self.cursors[index] as usize] {
-            CellState::House(_, y)
-            | CellState::House2(_, y)
+        match self.sim.grid[self.sim.cursors[index] as usize] {
+            CellState::House(_, y, _)
+            | CellState::House2(_, y, _)
             | CellState::BigRock(y)
-            | CellState::Tree(y)
-            | CellState::Stand(y) => {
+            | CellState::Tree(y, _)
+            | CellState::Stand(y, _) => {
                 if y % 2 == 0 { offset_x2 = 10 } else { offset_x = -10 }
                 if y > 1 { offset_y = -10 } else { offset_y2 = 10 }
             }
@@ -1136,7 +1385,7 @@ impl Game {
                 if y == 1 { offset_y = -10 } else { offset_y2 = 10 }
             }
 
-            CellState::Farm(y) | CellState::Hay(y) => {
+            CellState::Farm(y, ..) | CellState::Hay(y, _) => {
                 if y % 2 == 0 { offset_x2 = 10 } else { offset_x = -10 }
             }
 
@@ -1198,7 +1447,7 @@ impl Game {
             }
         }
 
-        for Entity { position_x, position_y, .. } in self.entities.iter() {
+        for Entity { position_x, position_y, .. } in self.sim.entities.iter() {
             Self::set_rect_colors(Color::Darker, Color::Darker);
             rect(MINIMAP_PIXEL_OFFSET_X+(*position_x as i32 / CELL_SIZE as i32), MINIMAP_PIXEL_OFFSET_Y+(*position_y as i32 / CELL_SIZE as i32), 1, 1);
         }
@@ -1206,18 +1455,20 @@ impl Game {
         for x in 0..GRID_SIZE_X {
             for y in 0..GRID_SIZE_Y {
                 let pos = grid_from_vec(x, y);
-                let cell = self.grid[pos as usize];
+                let cell = self.sim.grid[pos as usize];
 
 
 
                 let color = match cell {
                     CellState::Empty => Color::Transparent,
-                    CellState::House(BuildingState::Solid, _) | CellState::House2(BuildingState::Solid, _) | CellState::Church(BuildingState::Solid, _, _) => Color::Lighter,
-                    CellState::House(BuildingState::Burning, j) => flash_my_shit_twin(2, j, pos, self.tick),
-                    CellState::House2(BuildingState::Burning, j) => flash_my_shit_twin(2, j, pos, self.tick),
-                    CellState::Church(BuildingState::Burning, j, _) => flash_my_shit_twin(2, j, pos, self.tick),
-                    CellState::House(_, _) | CellState::House2(_, _) | CellState::Church(_, _, _) => Color::Darkest,
-                    CellState::BigRock(_) | CellState::Rock | CellState::Lamppost(_) | CellState::Bell | CellState::Tree(_) | CellState::Stand(_) | CellState::Farm(_) | CellState::Hay(_) => Color::Lighter,
+                    CellState::House(BuildingState::Solid, _, _) | CellState::House2(BuildingState::Solid, _, _) | CellState::Church(BuildingState::Solid, _, _) => Color::Lighter,
+                    CellState::House(BuildingState::Burning, j, _) => flash_my_shit_twin(2, j, pos, self.sim.tick),
+                    CellState::House2(BuildingState::Burning, j, _) => flash_my_shit_twin(2, j, pos, self.sim.tick),
+                    CellState::Church(BuildingState::Burning, j, _) => flash_my_shit_twin(2, j, pos, self.sim.tick),
+                    CellState::Tree(j, 1..) | CellState::Stand(j, 1..) | CellState::Hay(j, 1..) | CellState::Farm(j, _, 1..) => flash_my_shit_twin(2, j, pos, self.sim.tick),
+                    CellState::Barricade(1..) => flash_my_shit_twin(1, 0, pos, self.sim.tick),
+                    CellState::House(..) | CellState::House2(..) | CellState::Church(..) => Color::Darkest,
+                    CellState::BigRock(_) | CellState::Rock | CellState::Lamppost(_) | CellState::Bell(..) | CellState::Tree(..) | CellState::Stand(..) | CellState::Farm(..) | CellState::Hay(..) | CellState::Barricade(_) => Color::Lighter,
                 };
 
                 if color != Color::Transparent {
@@ -1226,6 +1477,15 @@ impl Game {
                 }
             }
         }
+
+        // rally flag on top of everything else
+        if let Some(flag) = self.sim.rally[self.current_player as usize] {
+            let (x, y) = vec_from_grid(flag);
+            let (x, y) = (MINIMAP_PIXEL_OFFSET_X + x as i32, MINIMAP_PIXEL_OFFSET_Y + y as i32);
+            Self::set_rect_colors(Color::Darkest, Color::Darkest);
+            rect(x, y - 2, 1, 3);
+            rect(x + 1, y - 2, 1, 1);
+        }
     }
 }
 
@@ -1243,17 +1503,32 @@ fn vec_from_grid(index: u16) -> (u8, u8) {
 
 // Checks if a grid pixel location is within bounds
 fn pixel_loc_in_grid_bounds(x: i16, y: i16) -> bool {
-    let x = x >= 0 && x < CELL_SIZE as i16 * (GRID_SIZE_X as i16 - 1);
-    let y = y >= 0 && y < CELL_SIZE as i16 * (GRID_SIZE_Y as i16 - 1);
+    let x = x >= 0 && x < CELL_SIZE as i16 * GRID_SIZE_X as i16;
+    let y = y >= 0 && y < CELL_SIZE as i16 * GRID_SIZE_Y as i16;
     x && y
 }
 
-// Apply a direction in index based space
-fn apply_direction(index: u16, dir: Direction) -> Option<u16> {
-    let (x, y) = vec_from_grid(index);
-    let (mut x, mut y) = (x as i8, y as i8);
+// Hitbox of an entity inside of its 10x10 sprite. Smaller than a cell, so it never overlaps more
+// than 2 cells on each axis and checking its corners is enough
+const HITBOX_MIN: i16 = 2;
+const HITBOX_MAX: i16 = 7;
+
+// Checks if an entity at the given pixel position would only overlap empty cells
+fn entity_fits(board: &Board, x: i16, y: i16) -> bool {
+    [(HITBOX_MIN, HITBOX_MIN), (HITBOX_MAX, HITBOX_MIN), (HITBOX_MIN, HITBOX_MAX), (HITBOX_MAX, HITBOX_MAX)]
+        .into_iter()
+        .all(|(offset_x, offset_y)| {
+            let (x, y) = (x + offset_x, y + offset_y);
+            pixel_loc_in_grid_bounds(x, y) && {
+                let cell = grid_from_vec((x / CELL_SIZE as i16) as u8, (y / CELL_SIZE as i16) as u8);
+                matches!(board[cell as usize], CellState::Empty)
+            }
+        })
+}
 
-    let offset = match dir {
+// Offset on the grid (or in pixels) of one step in a direction
+fn direction_offset(dir: Direction) -> (i8, i8) {
+    match dir {
         Direction::N => (0, -1),
         Direction::E => (1, 0),
         Direction::S => (0, 1),
@@ -1262,13 +1537,22 @@ fn apply_direction(index: u16, dir: Direction) -> Option<u16> {
         Direction::SE => (1, 1),
         Direction::NW => (-1, -1),
         Direction::SW => (-1, 1),
-    };
-
-    x += offset.0;
-    y += offset.1;
+    }
+}
 
-    let (x, y) = (x as u8, y as u8);
-    ((0..GRID_SIZE_Y).contains(&y) && (0..GRID_SIZE_X).contains(&x)).then(|| grid_from_vec(x, y))
+// Direction of a (non-zero) offset, only looks at the signs
+fn direction_from_offset(x: i8, y: i8) -> Direction {
+    match (x.signum(), y.signum()) {
+        (0, -1) => Direction::N,
+        (1, 0) => Direction::E,
+        (0, 1) => Direction::S,
+        (-1, 0) => Direction::W,
+        (1, -1) => Direction::NE,
+        (1, 1) => Direction::SE,
+        (-1, -1) => Direction::NW,
+        (-1, 1) => Direction::SW,
+        _ => Direction::E,
+    }
 }
 
 // calculate the bottom-left "root" position of the bigsprite given a cell state
@@ -1292,6 +1576,65 @@ fn calculate_big_sprite_root_position(
     return (building_root_x as u8, building_root_y as u8);
 }
 
+// width, height and sub-cell index of the (possibly multi-cell) thing occupying a cell
+fn building_dimensions(cell: &CellState) -> Option<(u8, u8, u8)> {
+    match *cell {
+        CellState::Empty => None,
+        CellState::House(_, j, _)
+        | CellState::House2(_, j, _)
+        | CellState::BigRock(j)
+        | CellState::Tree(j, _)
+        | CellState::Stand(j, _) => Some((2, 2, j)),
+        CellState::Church(_, j, _) => Some((2, 3, j)),
+        CellState::Lamppost(j) => Some((1, 2, j)),
+        CellState::Farm(j, ..) | CellState::Hay(j, _) => Some((2, 1, j)),
+        CellState::Rock | CellState::Bell(..) | CellState::Barricade(_) => Some((1, 1, 0)),
+    }
+}
+
+// state and integrity of the house, church or bell occupying a cell
+fn building_integrity(cell: &CellState) -> Option<(BuildingState, u8)> {
+    match *cell {
+        CellState::House(state, _, integrity) | CellState::House2(state, _, integrity) | CellState::Church(state, _, integrity) | CellState::Bell(state, integrity) => Some((state, integrity)),
+        _ => None,
+    }
+}
+
+// all the cells a building is made of
+fn building_cells(
+    building_bigsprite_width: u8,
+    building_bigsprite_height: u8,
+    big_sprite_subcell_index: u8,
+    cursor_grid_pos: u16,
+) -> impl Iterator<Item = u16> {
+    let (root_x, root_y) = calculate_big_sprite_root_position(building_bigsprite_width, big_sprite_subcell_index, cursor_grid_pos);
+    (0..building_bigsprite_width * building_bigsprite_height)
+        .map(move |j| grid_from_vec(root_x + j % building_bigsprite_width, root_y + j / building_bigsprite_width))
+}
+
+// all the cells on the skirts of a building (on the outline) that are inside the map
+// they might be occupied though
+fn building_outline(
+    building_bigsprite_width: u8,
+    building_bigsprite_height: u8,
+    big_sprite_subcell_index: u8,
+    cursor_grid_pos: u16,
+) -> impl Iterator<Item = u16> {
+    // calculate the root position of the building
+    let (root_x, root_y) = calculate_big_sprite_root_position(building_bigsprite_width, big_sprite_subcell_index, cursor_grid_pos);
+    let (root_x, root_y) = (root_x as i16, root_y as i16);
+    let (width, height) = (building_bigsprite_width as i16, building_bigsprite_height as i16);
+
+    ((root_x - 1)..(root_x + width + 1))
+        .flat_map(move |x| ((root_y - 1)..(root_y + height + 1)).map(move |y| (x, y)))
+        .filter(move |&(x, y)| {
+            let inside = (root_x..root_x + width).contains(&x) && (root_y..root_y + height).contains(&y);
+            let in_map = x >= 0 && x < GRID_SIZE_X as i16 && y >= 0 && y < GRID_SIZE_Y as i16;
+            !inside && in_map
+        })
+        .map(|(x, y)| grid_from_vec(x as u8, y as u8))
+}
+
 // picks a random location on the skirts of a building (on the outline)
 // returns None if the building is completely surrounded
 // returns Some with a position of a cell if it DID find a valid cell
@@ -1301,6 +1644,7 @@ fn pick_random_location_building_outline(
     building_bigsprite_height: u8,
     big_sprite_subcell_index: u8,
     cursor_grid_pos: u16,
+    rng: &mut fastrand::Rng,
 ) -> Option<u16> {
     /* 
         pseudocode
@@ -1310,56 +1654,35 @@ fn pick_random_location_building_outline(
         - pick random cell in the range of that array
     */
     
-    // calculate the root position of the building
-    let (building_root_x, building_root_y) = calculate_big_sprite_root_position(building_bigsprite_width, big_sprite_subcell_index, cursor_grid_pos);
-    let building_root_x = building_root_x as i16;
-    let building_root_y = building_root_y as i16;
-
-    // go over the outline cells of the building
-    let mut possible_cells= Vec::<u16>::new();
-    for x in (building_root_x - 1)..(building_root_x + building_bigsprite_width as i16 + 1)  {
-        for y in (building_root_y - 1)..(building_root_y + building_bigsprite_height as i16 + 1)  {
-            // make sure to discard cells that are outside the bounds of the map
-            if x >= 0 && x < GRID_SIZE_X as i16 && y >= 0 && y < GRID_SIZE_Y as i16 {
-                possible_cells.push(grid_from_vec(x as u8, y as u8))
-            }
-        }
-    }
-
-    // first step: get rid of cells that are occupied
-    // this also discards cells that are *inside* the building themselves
-    let possible_cells: Vec::<u16> = possible_cells.into_iter().filter(|position: &u16| matches!(board[*position as usize], CellState::Empty)).collect();
+    // go over the outline cells of the building, getting rid of cells that are occupied
+    let possible_cells: Vec::<u16> = building_outline(building_bigsprite_width, building_bigsprite_height, big_sprite_subcell_index, cursor_grid_pos)
+        .filter(|position: &u16| matches!(board[*position as usize], CellState::Empty))
+        .collect();
 
     // second step: pick a random cell if we can
     if possible_cells.len() == 0 {
         None
     } else {
-        let rng = fastrand::usize(0..possible_cells.len());
-        Some(possible_cells[rng])
+        // fastrand's usize draws depend on the pointer width, so the cart and the host would pick different cells
+        let index = rng.u32(0..possible_cells.len() as u32) as usize;
+        Some(possible_cells[index])
     }
 }
 
-fn get_neighbours(index: u16) -> [u16; 8] {
-    let y = GRID_SIZE_X as i16;
-    let index = index as i16;
-    let mut r = [u16::MAX; 8];
-
-    for result in [
-        index + y,
-        index + 1 + y,
-        index + 1,
-        index + 1 - y,
-        index - y,
-        index - 1 - y,
-        index - 1,
-        index - 1 + y,
-    ].iter().enumerate() {
-        trace("hi".to_owned() + &result.0.to_string());
-        let (x, y) = vec_from_grid(*result.1 as u16);
-        if (0..GRID_SIZE_Y).contains(&y) && (0..GRID_SIZE_X).contains(&x) { r[result.0] = *result.1 as u16 } 
-    }
+// Offsets of the neighbours returned by get_neighbours (S, SE, E, NE, N, NW, W, SW)
+const NEIGHBOUR_OFFSETS: [(i8, i8); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
 
-    return r
+// Indices of the 8 neighbours of a cell, u16::MAX for the ones outside the map
+fn get_neighbours(index: u16) -> [u16; 8] {
+    let (x, y) = vec_from_grid(index);
+    NEIGHBOUR_OFFSETS.map(|(offset_x, offset_y)| {
+        let (x, y) = (x.wrapping_add_signed(offset_x), y.wrapping_add_signed(offset_y));
+        if x < GRID_SIZE_X && y < GRID_SIZE_Y {
+            grid_from_vec(x, y)
+        } else {
+            u16::MAX
+        }
+    })
 }
 
 /*
@@ -1396,12 +1719,12 @@ fn get_neighbours_in_range(index: u16, range_x: Range<i16>, range_y: Range<i16>)
 }
 */
 
-fn random_direction() -> Direction {
-    unsafe { transmute::<u8, Direction>(fastrand::u8(0..4)) }
+fn random_direction(rng: &mut fastrand::Rng) -> Direction {
+    unsafe { transmute::<u8, Direction>(rng.u8(0..4)) }
 }
 
-fn random_direction_with_diagonal() -> Direction {
-    unsafe { transmute::<u8, Direction>(fastrand::u8(0..8)) }
+fn random_direction_with_diagonal(rng: &mut fastrand::Rng) -> Direction {
+    unsafe { transmute::<u8, Direction>(rng.u8(0..8)) }
 }
 
 #[no_mangle]
@@ -1413,3 +1736,192 @@ unsafe fn start() {
 unsafe fn update() {
     GAME.as_mut().unwrap().run();
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::wasm4::host;
+    use std::alloc::{GlobalAlloc, Layout, System};
+
+    // Same as in alloc.rs, where only the wasm32 build can see it
+    const HEAP_SIZE: usize = 16 * 1024;
+
+    // Room the buddy allocator takes out of the heap for its own lists and bitmaps
+    const BUDDY_BOOKKEEPING: usize = 1024;
+
+    thread_local! {
+        // bytes the buddy heap would have handed out on this thread (now and at most)
+        static HEAP: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+    }
+
+    // The host allocator, keeping count of what the same allocations would take on the cart: blocks
+    // of at least 16 bytes, rounded up to a power of two. Small ones would go to the fast heap first,
+    // counting them here too only makes it stricter
+    struct CountingAlloc;
+
+    fn buddy_block(layout: Layout) -> usize {
+        layout.size().max(layout.align()).next_power_of_two().max(16)
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = HEAP.try_with(|heap| {
+                let (live, peak) = heap.get();
+                let live = live + buddy_block(layout);
+                heap.set((live, peak.max(live)));
+            });
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let _ = HEAP.try_with(|heap| {
+                let (live, peak) = heap.get();
+                heap.set((live.saturating_sub(buddy_block(layout)), peak));
+            });
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    // Runs one frame of the game with both gamepads set
+    fn frame(game: &mut Game, pads: [u8; 2]) {
+        host::set_gamepad(0, pads[0]);
+        host::set_gamepad(1, pads[1]);
+        host::begin_frame();
+        unsafe { game.run() };
+    }
+
+    #[test]
+    fn pausing_keeps_half_of_the_combo_out_of_the_match() {
+        let _lock = golden::lock();
+        host::reset();
+        let mut game = unsafe { Game::with_seed(1) };
+        frame(&mut game, [0, 0]);
+
+        // B1 a frame before B2 still pauses, without the match ever seeing B1
+        frame(&mut game, [BUTTON_1, 0]);
+        frame(&mut game, [BUTTON_1 | BUTTON_2, 0]);
+        assert_eq!(game.screen, Screen::Paused);
+        assert_eq!(game.sim.old_gamepad, [0, 0]);
+        assert_eq!(game.recording.frames(), 2);
+
+        // nothing moves until the combo comes again, and the buttons are ignored until they're let go
+        let checksum = game.sim.checksum();
+        frame(&mut game, [0, 0]);
+        assert_eq!(game.sim.checksum(), checksum);
+        frame(&mut game, [BUTTON_1 | BUTTON_2, 0]);
+        assert_eq!(game.screen, Screen::Playing);
+        frame(&mut game, [BUTTON_1 | BUTTON_2, 0]);
+        assert_eq!(game.sim.old_gamepad, [0, 0]);
+
+        // a tap that's over before the match gets it still counts
+        frame(&mut game, [0, 0]);
+        frame(&mut game, [0, BUTTON_1]);
+        frame(&mut game, [0, 0]);
+        assert_eq!(game.sim.old_gamepad, [0, BUTTON_1]);
+        assert_eq!(game.screen, Screen::Playing);
+    }
+
+    #[test]
+    fn b2_leaves_a_paused_match_for_the_title() {
+        let _lock = golden::lock();
+        host::reset();
+        let mut game = unsafe { Game::with_seed(1) };
+        let seed = game.setup.seed;
+        frame(&mut game, [0, 0]);
+
+        // pressing B2 first then B1 resumes instead of quitting
+        frame(&mut game, [BUTTON_1 | BUTTON_2, 0]);
+        frame(&mut game, [0, 0]);
+        assert_eq!(game.screen, Screen::Paused);
+        frame(&mut game, [BUTTON_2, 0]);
+        frame(&mut game, [BUTTON_1 | BUTTON_2, 0]);
+        assert_eq!(game.screen, Screen::Playing);
+        frame(&mut game, [0, 0]);
+
+        // B2 on its own quits once it's let go
+        frame(&mut game, [BUTTON_1 | BUTTON_2, 0]);
+        frame(&mut game, [0, 0]);
+        frame(&mut game, [0, BUTTON_2]);
+        assert_eq!(game.screen, Screen::Paused);
+        frame(&mut game, [0, 0]);
+        assert_eq!(game.screen, Screen::Title);
+        assert_eq!(game.setup.seed, seed + 1);
+    }
+
+    #[test]
+    fn matches_and_replays_fit_in_the_buddy_heap() {
+        let _lock = golden::lock();
+        host::reset();
+        HEAP.with(|heap| heap.set((0, 0)));
+
+        // a match that runs for a while, summoning on both sides, then paused and left for the title
+        let mut game = unsafe { Game::new() };
+        unsafe { game.start_match() };
+        let house = (0..AREA as u16).find(|cell| matches!(game.sim.grid[*cell as usize], CellState::House(BuildingState::Solid, ..))).unwrap();
+        let (house_x, house_y) = vec_from_grid(house);
+        for i in 0..600u32 {
+            let (x, y) = vec_from_grid(game.sim.cursors[0]);
+            let p1 = if matches!(game.sim.grid[game.sim.cursors[0] as usize], CellState::House(..)) {
+                if i % 16 < 8 { BUTTON_1 } else { 0 }
+            } else if x != house_x {
+                if x < house_x { BUTTON_RIGHT } else { BUTTON_LEFT }
+            } else if y < house_y {
+                BUTTON_DOWN
+            } else {
+                BUTTON_UP
+            };
+            frame(&mut game, [p1, if i % 30 < 15 { BUTTON_1 } else { BUTTON_RIGHT }]);
+        }
+        frame(&mut game, [BUTTON_1 | BUTTON_2, 0]);
+        assert_eq!(game.screen, Screen::Paused);
+        assert!(game.sim.entities.len() > 2);
+        let mut overall = switch(&mut game, |game| game.back_to_title());
+
+        // then watched again, and left for the title once more
+        overall = overall.max(switch(&mut game, |game| assert!(game.replay_last_match())));
+        for _ in 0..600 {
+            frame(&mut game, [0, 0]);
+        }
+        overall = overall.max(switch(&mut game, |game| game.back_to_title()));
+        overall = overall.max(switch(&mut game, |game| unsafe { game.start_match() }));
+
+        assert!(overall <= HEAP_SIZE - BUDDY_BOOKKEEPING, "peak heap usage of {overall} bytes");
+    }
+
+    // Runs a change of screen, making sure it never holds two boards at once. Returns the peak heap
+    // usage since the start of the test
+    fn switch(game: &mut Game, change: impl FnOnce(&mut Game)) -> usize {
+        let (live, overall) = HEAP.with(|heap| heap.replace((heap.get().0, 0)));
+        change(game);
+        let (_, peak) = HEAP.with(|heap| heap.get());
+        assert!(peak < live + size_of::<[CellState; AREA]>(), "{} more bytes on the heap", peak - live);
+        overall.max(peak)
+    }
+
+    #[test]
+    fn truncated_replays_go_back_to_the_title() {
+        let _lock = golden::lock();
+        host::reset();
+        let mut game = unsafe { Game::with_seed(1) };
+        let mut recording = Recording::with_setup(game.setup, 64);
+        (0..1000u32).for_each(|frame| recording.record([(frame / 5 % 2) as u8 * BUTTON_RIGHT, 0]));
+        assert!(recording.truncated());
+        let frames = recording.frames();
+        game.save = Some(Save { replay: Some(recording), ..Default::default() });
+
+        // whatever the menus left behind doesn't carry over into the replay
+        (game.held, game.deferred) = (0, [BUTTON_1, BUTTON_2]);
+        assert!(game.replay_last_match());
+        assert_eq!((game.held, game.deferred), (BUTTON_1 | BUTTON_2, [0, 0]));
+        for _ in 0..frames {
+            frame(&mut game, [0, 0]);
+            assert_eq!(game.screen, Screen::Playing);
+        }
+        frame(&mut game, [0, 0]);
+        assert_eq!(game.screen, Screen::Title);
+        assert!(game.replay.is_none());
+    }
+}
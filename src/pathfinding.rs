@@ -0,0 +1,315 @@
+// Grid pathfinding (A*) over the board
+//
+// Entities can only walk on empty cells, and they can't cut the corner of an occupied cell when
+// going diagonally. All the bookkeeping lives on the stack (about 3.3 KB, see STACK_USAGE), so looking
+// for a path never touches the 16 KB buddy heap
+
+use crate::*;
+
+// Cost of a straight / diagonal step, roughly 1 : sqrt(2)
+pub const STRAIGHT_COST: u16 = 10;
+pub const DIAGONAL_COST: u16 = 14;
+
+// Max number of cells waiting in the open list, which bounds the stack usage
+// Cells that don't fit are dropped, which at worst makes us miss a path on a really twisted map
+const OPEN_LIST_SIZE: usize = 256;
+
+// Max number of goal cells (outline of the biggest building, a 2x3 church, has 14)
+pub(crate) const MAX_GOALS: usize = 16;
+
+// Stack of the cart, see -zstack-size in .cargo/config.toml (minus the 6560 bytes it's offset from)
+const STACK_SIZE: usize = 8192;
+
+// Bookkeeping of next_step: costs, directions, open list and goals. FlowField::compute needs the same
+// minus the directions, and both get called straight from the simulation (never one inside of the
+// other), so this is the most pathfinding ever puts on the stack. The other half is left for the
+// frames of the simulation calling in here
+pub(crate) const STACK_USAGE: usize = size_of::<[u16; AREA]>() + size_of::<Nibbles>() + size_of::<OpenList>() + size_of::<[u16; MAX_GOALS]>();
+const _: () = assert!(STACK_USAGE <= STACK_SIZE / 2);
+
+// A value between 0 and 15 for every cell of the board, packed two per byte (450 bytes)
+pub(crate) type Nibbles = [u8; AREA / 2];
+
+pub(crate) fn nibble(nibbles: &Nibbles, index: u16) -> u8 {
+    (nibbles[index as usize / 2] >> (index % 2 * 4)) & 0xf
+}
+
+pub(crate) fn set_nibble(nibbles: &mut Nibbles, index: u16, value: u8) {
+    let byte = &mut nibbles[index as usize / 2];
+    let shift = index % 2 * 4;
+    *byte = (*byte & !(0xf << shift)) | value << shift;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Goal {
+    // A single (empty) cell
+    Cell(u16),
+
+    // Any sub-cell of a building, we go to the closest free cell on its outline
+    Building(u16),
+}
+
+fn is_empty(board: &Board, index: u16) -> bool {
+    index != u16::MAX && matches!(board[index as usize], CellState::Empty)
+}
+
+// Cost of a step in one of the get_neighbours directions
+pub fn step_cost(direction: usize) -> u16 {
+    let (x, y) = NEIGHBOUR_OFFSETS[direction];
+    if x != 0 && y != 0 {
+        DIAGONAL_COST
+    } else {
+        STRAIGHT_COST
+    }
+}
+
+// Neighbours we can walk to from a cell, as (direction, neighbour index)
+pub fn walkable_neighbours(board: &Board, index: u16) -> impl Iterator<Item = (usize, u16)> + '_ {
+    let (x, y) = vec_from_grid(index);
+    get_neighbours(index)
+        .into_iter()
+        .enumerate()
+        .filter(move |&(direction, neighbour)| {
+            let (offset_x, offset_y) = NEIGHBOUR_OFFSETS[direction];
+            let diagonal = offset_x != 0 && offset_y != 0;
+
+            // going diagonally needs both of the sides to be free too, otherwise we'd cut a corner
+            // the sides are always in the map if the diagonal is
+            is_empty(board, neighbour)
+                && (!diagonal
+                    || (is_empty(board, grid_from_vec(x.wrapping_add_signed(offset_x), y))
+                        && is_empty(board, grid_from_vec(x, y.wrapping_add_signed(offset_y)))))
+        })
+}
+
+// Octile distance, never overestimates the cost of a path
+pub fn distance(a: u16, b: u16) -> u16 {
+    let (ax, ay) = vec_from_grid(a);
+    let (bx, by) = vec_from_grid(b);
+    let (x, y) = (ax.abs_diff(bx) as u16, ay.abs_diff(by) as u16);
+    STRAIGHT_COST * x.max(y) + (DIAGONAL_COST - STRAIGHT_COST) * x.min(y)
+}
+
+// Fixed size binary min-heap of (f score, cell index) packed in an u32
+// Ties are broken on the index, so the paths are deterministic
+pub(crate) struct OpenList {
+    items: [u32; OPEN_LIST_SIZE],
+    len: usize,
+}
+
+impl OpenList {
+    pub(crate) fn new() -> Self {
+        Self {
+            items: [0; OPEN_LIST_SIZE],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, score: u16, index: u16) {
+        if self.len == OPEN_LIST_SIZE {
+            return;
+        }
+
+        let mut i = self.len;
+        self.items[i] = (score as u32) << 16 | index as u32;
+        self.len += 1;
+
+        while i > 0 && self.items[(i - 1) / 2] > self.items[i] {
+            self.items.swap(i, (i - 1) / 2);
+            i = (i - 1) / 2;
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<(u16, u16)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let top = self.items[0];
+        self.len -= 1;
+        self.items[0] = self.items[self.len];
+
+        let mut i = 0;
+        loop {
+            let smallest = [2 * i + 1, 2 * i + 2]
+                .into_iter()
+                .filter(|child| *child < self.len)
+                .fold(i, |smallest, child| if self.items[child] < self.items[smallest] { child } else { smallest });
+
+            if smallest == i {
+                break;
+            }
+
+            self.items.swap(i, smallest);
+            i = smallest;
+        }
+
+        Some(((top >> 16) as u16, top as u16))
+    }
+}
+
+// Empty cells that count as reaching the goal, returns how many were written
+pub(crate) fn goal_cells(board: &Board, goal: Goal, cells: &mut [u16; MAX_GOALS]) -> usize {
+    match goal {
+        Goal::Cell(index) if is_empty(board, index) => {
+            cells[0] = index;
+            1
+        }
+
+        Goal::Cell(_) => 0,
+
+        Goal::Building(index) => {
+            let Some((width, height, j)) = building_dimensions(&board[index as usize]) else {
+                return 0;
+            };
+
+            let mut count = 0;
+            for cell in building_outline(width, height, j, index).filter(|cell| is_empty(board, *cell)) {
+                cells[count] = cell;
+                count += 1;
+            }
+            count
+        }
+    }
+}
+
+// Finds a shortest path from `start` to `goal` and returns the first cell to walk to
+// Returns Some(start) if we're already there, and None if the goal can't be reached
+pub fn next_step(board: &Board, start: u16, goal: Goal) -> Option<u16> {
+    let mut goals = [u16::MAX; MAX_GOALS];
+    let goal_count = goal_cells(board, goal, &mut goals);
+    let goals = &goals[..goal_count];
+    if goals.contains(&start) {
+        return Some(start);
+    } else if goals.is_empty() {
+        return None;
+    }
+
+    let heuristic = |index: u16| goals.iter().map(|goal| distance(index, *goal)).min().unwrap_or_default();
+
+    // cheapest known cost to reach each cell, and the direction we took to get there
+    let mut costs = [u16::MAX; AREA];
+    let mut came_from: Nibbles = [0xff; AREA / 2];
+    let mut open = OpenList::new();
+
+    costs[start as usize] = 0;
+    open.push(heuristic(start), start);
+
+    while let Some((score, current)) = open.pop() {
+        if goals.contains(&current) {
+            return Some(first_step(&came_from, start, current));
+        }
+
+        // we already found a cheaper way to this cell since it was pushed
+        let cost = costs[current as usize];
+        if score > cost + heuristic(current) {
+            continue;
+        }
+
+        for (direction, neighbour) in walkable_neighbours(board, current) {
+            let new_cost = cost + step_cost(direction);
+            if new_cost < costs[neighbour as usize] {
+                costs[neighbour as usize] = new_cost;
+                set_nibble(&mut came_from, neighbour, direction as u8);
+                open.push(new_cost + heuristic(neighbour), neighbour);
+            }
+        }
+    }
+
+    None
+}
+
+// Walks the path back from the goal until we're next to the start
+fn first_step(came_from: &Nibbles, start: u16, goal: u16) -> u16 {
+    let mut current = goal;
+    loop {
+        let (x, y) = vec_from_grid(current);
+        let (offset_x, offset_y) = NEIGHBOUR_OFFSETS[nibble(came_from, current) as usize];
+        let previous = grid_from_vec(x.wrapping_add_signed(-offset_x), y.wrapping_add_signed(-offset_y));
+
+        if previous == start {
+            return current;
+        }
+
+        current = previous;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_board() -> Board {
+        Box::new([CellState::Empty; AREA])
+    }
+
+    // Follows the path step by step, returns the cells we went through
+    fn walk(board: &Board, start: u16, goal: Goal) -> Option<Vec<u16>> {
+        let mut path = vec![start];
+        loop {
+            let current = *path.last().unwrap();
+            let next = next_step(board, current, goal)?;
+            if next == current {
+                return Some(path);
+            }
+
+            assert!(path.len() < AREA, "path is looping");
+            path.push(next);
+        }
+    }
+
+    #[test]
+    fn straight_and_diagonal_paths() {
+        let board = empty_board();
+        let path = walk(&board, grid_from_vec(2, 2), Goal::Cell(grid_from_vec(12, 2))).unwrap();
+        assert_eq!(path.len(), 11);
+
+        let path = walk(&board, grid_from_vec(2, 2), Goal::Cell(grid_from_vec(7, 7))).unwrap();
+        assert_eq!(path.len(), 6);
+    }
+
+    #[test]
+    fn goes_around_walls() {
+        let mut board = empty_board();
+        for y in 0..GRID_SIZE_Y - 1 {
+            board[grid_from_vec(5, y) as usize] = CellState::Rock;
+        }
+
+        let path = walk(&board, grid_from_vec(2, 2), Goal::Cell(grid_from_vec(8, 2))).unwrap();
+        assert!(path.iter().all(|cell| matches!(board[*cell as usize], CellState::Empty)));
+        assert!(path.contains(&grid_from_vec(5, GRID_SIZE_Y - 1)));
+    }
+
+    #[test]
+    fn does_not_cut_corners() {
+        let mut board = empty_board();
+        board[grid_from_vec(3, 2) as usize] = CellState::Rock;
+
+        // (2, 2) -> (3, 3) would cut the corner of the rock
+        let next = next_step(&board, grid_from_vec(2, 2), Goal::Cell(grid_from_vec(3, 3))).unwrap();
+        assert_eq!(next, grid_from_vec(2, 3));
+    }
+
+    #[test]
+    fn unreachable_goals() {
+        let mut board = empty_board();
+        for cell in get_neighbours(grid_from_vec(10, 10)) {
+            board[cell as usize] = CellState::Rock;
+        }
+
+        assert!(next_step(&board, grid_from_vec(2, 2), Goal::Cell(grid_from_vec(10, 10))).is_none());
+        assert!(next_step(&board, grid_from_vec(2, 2), Goal::Cell(grid_from_vec(10, 9))).is_none());
+    }
+
+    #[test]
+    fn buildings_are_reached_from_their_outline() {
+        let mut board = empty_board();
+        for j in 0..6 {
+            board[grid_from_vec(10 + j % 2, 10 + j / 2) as usize] = CellState::Church(BuildingState::Solid, j, CHURCH_INTEGRITY);
+        }
+
+        // aim at the bottom right sub-cell, we should still stop at the closest side
+        let path = walk(&board, grid_from_vec(2, 11), Goal::Building(grid_from_vec(11, 12))).unwrap();
+        assert_eq!(*path.last().unwrap(), grid_from_vec(9, 11));
+    }
+}
@@ -0,0 +1,294 @@
+// Input recordings of a whole match, small enough to fit on the WASM-4 disk
+//
+// Both gamepads are stored once per frame, run-length encoded since they rarely change
+// Encoded layout (little endian):
+//   0..8   seed of the match
+//   8..12  rest of the match setup (see `MatchSetup::encode`)
+//   12..14 number of runs
+//   14..   runs, 3 bytes each: p1 gamepad, p2 gamepad, number of frames (1..=255)
+
+use crate::simulation::MatchSetup;
+
+// Size of the encoded header (seed + setup + run count)
+pub const HEADER_SIZE: usize = 8 + MatchSetup::ENCODED_SIZE + 2;
+
+// Size of a single encoded run
+pub const RUN_SIZE: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Run {
+    inputs: [u8; 2],
+    frames: u8,
+}
+
+#[derive(Clone)]
+pub struct Recording {
+    setup: MatchSetup,
+    runs: Vec<Run>,
+
+    // maximum number of runs we can store, anything after that is dropped
+    max_runs: usize,
+    truncated: bool,
+}
+
+impl Recording {
+    // Create an empty recording that fits in `max_size` bytes once encoded
+    pub fn new(seed: u64, max_size: usize) -> Self {
+        Self::with_setup(MatchSetup::new(seed), max_size)
+    }
+
+    pub fn with_setup(setup: MatchSetup, max_size: usize) -> Self {
+        Self {
+            setup,
+            runs: Vec::new(),
+            max_runs: max_size.saturating_sub(HEADER_SIZE) / RUN_SIZE,
+            truncated: false,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.setup.seed
+    }
+
+    pub fn setup(&self) -> MatchSetup {
+        self.setup
+    }
+
+    // Number of frames in the recording
+    pub fn frames(&self) -> u32 {
+        self.runs.iter().map(|run| run.frames as u32).sum()
+    }
+
+    // Whether we ran out of space and stopped recording
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    // Record the inputs of the next frame. Once the recording is full, the remaining frames are
+    // dropped so the recording stays a valid prefix of the match
+    pub fn record(&mut self, inputs: [u8; 2]) {
+        if self.truncated {
+            return;
+        }
+
+        if let Some(run) = self.runs.last_mut() {
+            if run.inputs == inputs && run.frames < u8::MAX {
+                run.frames += 1;
+                return;
+            }
+        }
+
+        if self.runs.len() < self.max_runs {
+            self.runs.push(Run { inputs, frames: 1 });
+        } else {
+            self.truncated = true;
+        }
+    }
+
+    pub fn encoded_size(&self) -> usize {
+        HEADER_SIZE + self.runs.len() * RUN_SIZE
+    }
+
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.setup.seed.to_le_bytes());
+        self.setup.encode(bytes);
+        bytes.extend_from_slice(&(self.runs.len() as u16).to_le_bytes());
+        for Run { inputs, frames } in self.runs.iter() {
+            bytes.extend_from_slice(&[inputs[0], inputs[1], *frames]);
+        }
+    }
+
+    // Decode a recording, returns None if the bytes don't hold a complete one
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let seed = u64::from_le_bytes(bytes.get(0..8)?.try_into().ok()?);
+        let setup = MatchSetup::decode(seed, bytes.get(8..HEADER_SIZE - 2)?);
+        let count = u16::from_le_bytes(bytes.get(HEADER_SIZE - 2..HEADER_SIZE)?.try_into().ok()?) as usize;
+        let runs = bytes
+            .get(HEADER_SIZE..HEADER_SIZE + count * RUN_SIZE)?
+            .chunks_exact(RUN_SIZE)
+            .map(|run| Run {
+                inputs: [run[0], run[1]],
+                frames: run[2],
+            })
+            .collect::<Vec<_>>();
+
+        // a run of 0 frames can't be written by us
+        if runs.iter().any(|run| run.frames == 0) {
+            return None;
+        }
+
+        Some(Self {
+            setup,
+            max_runs: runs.len(),
+            runs,
+            truncated: false,
+        })
+    }
+}
+
+// Plays a recording back one frame at a time
+pub struct Replay {
+    recording: Recording,
+    run: usize,
+    frame: u8,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            run: 0,
+            frame: 0,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.recording.seed()
+    }
+
+    // Inputs of the next frame, or None once the recording is over
+    pub fn next_inputs(&mut self) -> Option<[u8; 2]> {
+        let run = self.recording.runs.get(self.run)?;
+        self.frame += 1;
+        if self.frame == run.frames {
+            self.frame = 0;
+            self.run += 1;
+        }
+
+        Some(run.inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{MapSize, Mode};
+    use crate::wasm4::{BUTTON_1, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_UP};
+    use crate::{vec_from_grid, BuildingState, CellState, Simulation};
+
+    // Some made up inputs that change every few frames
+    fn inputs(frame: u32) -> [u8; 2] {
+        [(frame / 7 % 5) as u8 * 16, (frame / 13 % 3) as u8]
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut recording = Recording::new(42, 1024);
+        (0..1000).for_each(|frame| recording.record(inputs(frame)));
+
+        let mut bytes = Vec::new();
+        recording.encode(&mut bytes);
+        assert_eq!(bytes.len(), recording.encoded_size());
+
+        let mut replay = Replay::new(Recording::decode(&bytes).unwrap());
+        assert_eq!(replay.seed(), 42);
+        for frame in 0..1000 {
+            assert_eq!(replay.next_inputs(), Some(inputs(frame)));
+        }
+        assert_eq!(replay.next_inputs(), None);
+    }
+
+    #[test]
+    fn long_recordings_are_truncated() {
+        let mut recording = Recording::new(42, 64);
+        (0..1000).for_each(|frame| recording.record(inputs(frame)));
+        assert!(recording.truncated());
+
+        let mut bytes = Vec::new();
+        recording.encode(&mut bytes);
+        assert!(bytes.len() <= 64);
+
+        // what's left is still the start of the match
+        let mut replay = Replay::new(Recording::decode(&bytes).unwrap());
+        for frame in 0..recording.frames() {
+            assert_eq!(replay.next_inputs(), Some(inputs(frame)));
+        }
+        assert_eq!(replay.next_inputs(), None);
+    }
+
+    #[test]
+    fn corrupt_recordings_are_rejected() {
+        let mut recording = Recording::new(42, 1024);
+        (0..100).for_each(|frame| recording.record(inputs(frame)));
+        let mut bytes = Vec::new();
+        recording.encode(&mut bytes);
+
+        assert!(Recording::decode(&bytes[..bytes.len() - 1]).is_none());
+        assert!(Recording::decode(&[0; 4]).is_none());
+    }
+
+    #[test]
+    fn replay_matches_the_recorded_match() {
+        // the AI and the map size have to come back the same for the match to play out the same
+        let setup = MatchSetup { mode: Mode::Ai, map_size: MapSize::Medium, emeralds: [10, 250], seed: 7 };
+        let mut sim = Simulation::with_setup(&setup);
+        let mut recording = Recording::with_setup(setup, 1024);
+        for frame in 0..600 {
+            recording.record(inputs(frame));
+            sim.step(inputs(frame));
+        }
+
+        let mut bytes = Vec::new();
+        recording.encode(&mut bytes);
+        let recording = Recording::decode(&bytes).unwrap();
+        assert_eq!(recording.setup(), setup);
+
+        let mut replayed = Simulation::with_setup(&recording.setup());
+        let mut replay = Replay::new(recording);
+        while let Some(inputs) = replay.next_inputs() {
+            replayed.step(inputs);
+        }
+
+        assert_eq!(sim.checksum(), replayed.checksum());
+    }
+
+    #[test]
+    fn replays_play_out_the_same_on_every_platform() {
+        // the villagers walk the cursor over to the nearest house and keep spawning there, while the AI
+        // spawns on the border, both of which pick random cells
+        let setup = MatchSetup { mode: Mode::Ai, map_size: MapSize::Medium, emeralds: [250, 250], seed: 7 };
+        let mut sim = Simulation::with_setup(&setup);
+        let mut recording = Recording::with_setup(setup, 1024);
+        let house = |cell: &CellState| matches!(cell, CellState::House(BuildingState::Solid, ..));
+        let (cursor_x, cursor_y) = vec_from_grid(sim.cursors[0]);
+        let (target_x, target_y) = (0..sim.grid.len() as u16)
+            .filter(|cell| house(&sim.grid[*cell as usize]))
+            .map(vec_from_grid)
+            .min_by_key(|(x, y)| x.abs_diff(cursor_x) + y.abs_diff(cursor_y))
+            .unwrap();
+
+        for frame in 0..600 {
+            let (x, y) = vec_from_grid(sim.cursors[0]);
+            let p1 = if house(&sim.grid[sim.cursors[0] as usize]) {
+                if frame % 16 < 8 { BUTTON_1 } else { 0 }
+            } else if x < target_x {
+                BUTTON_RIGHT
+            } else if x > target_x {
+                BUTTON_LEFT
+            } else if y < target_y {
+                BUTTON_DOWN
+            } else {
+                BUTTON_UP
+            };
+
+            recording.record([p1, 0]);
+            sim.step([p1, 0]);
+        }
+        assert!(sim.entities.iter().any(|entity| !entity.is_illager()));
+        assert!(sim.entities.iter().any(|entity| entity.is_illager()));
+        assert!(!recording.truncated());
+
+        let mut bytes = Vec::new();
+        recording.encode(&mut bytes);
+        let recording = Recording::decode(&bytes).unwrap();
+        let mut replayed = Simulation::with_setup(&recording.setup());
+        let mut replay = Replay::new(recording);
+        while let Some(inputs) = replay.next_inputs() {
+            replayed.step(inputs);
+        }
+
+        // pinned, so a recording from the cart still plays out the same in the headless runner
+        assert_eq!(replayed.checksum(), sim.checksum());
+        assert_eq!(replayed.checksum(), 669244575727031723);
+    }
+}
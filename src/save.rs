@@ -0,0 +1,254 @@
+// Save data stored on the WASM-4 disk
+//
+// Layout (little endian):
+//   0..4   magic
+//   4      version
+//   5..9   checksum of everything after the header (low half of FNV-1a)
+//   9..    sections, each one is a tag (u8), a payload length (u16) and the payload
+//
+// Unknown sections are skipped and missing fields at the end of a section keep their default, so
+// sections can grow without bumping the version. Anything we can't make sense of (wrong magic,
+// newer version, bad checksum) gives a fresh save instead
+//
+// Before this format existed, the disk only held the seed as a bare u64
+
+use crate::replay::Recording;
+use crate::simulation::Fnv;
+use crate::wasm4::{diskr, diskw, DISK_SIZE};
+use std::hash::Hasher;
+
+const MAGIC: [u8; 4] = *b"VvsI";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 4 + 1 + 4;
+const SECTION_HEADER_SIZE: usize = 1 + 2;
+const LEGACY_SIZE: usize = std::mem::size_of::<u64>();
+
+// Section tags
+const SEED: u8 = 1;
+const SETTINGS: u8 = 2;
+const STATS: u8 = 3;
+const REPLAY: u8 = 4;
+
+// Payload sizes of the fixed sections
+const SEED_SIZE: usize = 8;
+const SETTINGS_SIZE: usize = 1;
+const STATS_SIZE: usize = 8;
+
+// Whatever is left of the disk once every other section is written
+pub const REPLAY_CAPACITY: usize = DISK_SIZE
+    - HEADER_SIZE
+    - 4 * SECTION_HEADER_SIZE
+    - SEED_SIZE
+    - SETTINGS_SIZE
+    - STATS_SIZE;
+
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub sound: bool,
+
+    // shows the 4 colors of the palette in a corner
+    pub debug_palette: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { sound: true, debug_palette: false }
+    }
+}
+
+// Lifetime stats, over every match played on this disk
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    pub matches: u32,
+    pub seconds_played: u32,
+}
+
+#[derive(Clone, Default)]
+pub struct Save {
+    pub seed: u64,
+    pub settings: Settings,
+    pub stats: Stats,
+    pub replay: Option<Recording>,
+}
+
+impl Save {
+    /// Reads the save from disk, falling back to a fresh one if there is none (or it's unreadable)
+    ///
+    /// # Safety
+    /// Must be called from the WASM-4 callbacks (or on the host backend)
+    pub unsafe fn load() -> Self {
+        let mut disk = [0u8; DISK_SIZE];
+        let len = diskr(disk.as_mut_ptr(), DISK_SIZE as u32) as usize;
+        Self::decode(&disk[..len.min(DISK_SIZE)])
+    }
+
+    /// Writes the save to disk
+    ///
+    /// # Safety
+    /// Must be called from the WASM-4 callbacks (or on the host backend)
+    pub unsafe fn store(&self) {
+        let bytes = self.encode();
+        diskw(bytes.as_ptr(), bytes.len() as u32);
+    }
+
+    pub fn decode(bytes: &[u8]) -> Self {
+        if bytes.len() == LEGACY_SIZE && !bytes.starts_with(&MAGIC) {
+            return Self {
+                seed: u64::from_le_bytes(bytes.try_into().unwrap()),
+                ..Default::default()
+            };
+        }
+
+        Self::decode_sections(bytes).unwrap_or_default()
+    }
+
+    fn decode_sections(bytes: &[u8]) -> Option<Self> {
+        let (header, mut body) = (bytes.get(..HEADER_SIZE)?, &bytes[HEADER_SIZE..]);
+        let checksum = u32::from_le_bytes(header[5..9].try_into().ok()?);
+        if header[..4] != MAGIC || header[4] != VERSION || checksum != Self::checksum(body) {
+            return None;
+        }
+
+        let mut save = Self::default();
+        while !body.is_empty() {
+            let tag = body[0];
+            let len = u16::from_le_bytes(body.get(1..3)?.try_into().ok()?) as usize;
+            let payload = body.get(SECTION_HEADER_SIZE..SECTION_HEADER_SIZE + len)?;
+            body = &body[SECTION_HEADER_SIZE + len..];
+
+            match tag {
+                SEED => save.seed = read_u64(payload, 0).unwrap_or_default(),
+                SETTINGS => {
+                    let flags = payload.first().copied().unwrap_or(1);
+                    save.settings.sound = flags & 1 != 0;
+                    save.settings.debug_palette = flags & 2 != 0;
+                }
+                STATS => {
+                    save.stats.matches = read_u32(payload, 0).unwrap_or_default();
+                    save.stats.seconds_played = read_u32(payload, 4).unwrap_or_default();
+                }
+                REPLAY => save.replay = Recording::decode(payload),
+                _ => {}
+            }
+        }
+
+        Some(save)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(DISK_SIZE);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&[0; 4]);
+
+        section(&mut bytes, SEED, |bytes| bytes.extend_from_slice(&self.seed.to_le_bytes()));
+        section(&mut bytes, SETTINGS, |bytes| bytes.push(self.settings.sound as u8 | (self.settings.debug_palette as u8) << 1));
+        section(&mut bytes, STATS, |bytes| {
+            bytes.extend_from_slice(&self.stats.matches.to_le_bytes());
+            bytes.extend_from_slice(&self.stats.seconds_played.to_le_bytes());
+        });
+
+        // drop the replay rather than writing a save that doesn't fit
+        if let Some(replay) = self.replay.as_ref().filter(|replay| replay.encoded_size() <= REPLAY_CAPACITY) {
+            section(&mut bytes, REPLAY, |bytes| replay.encode(bytes));
+        }
+
+        let checksum = Self::checksum(&bytes[HEADER_SIZE..]);
+        bytes[5..9].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    fn checksum(body: &[u8]) -> u32 {
+        let mut hasher = Fnv::default();
+        hasher.write(body);
+        hasher.finish() as u32
+    }
+}
+
+// Writes a section, filling in its length once the payload is written
+fn section(bytes: &mut Vec<u8>, tag: u8, payload: impl FnOnce(&mut Vec<u8>)) {
+    bytes.extend_from_slice(&[tag, 0, 0]);
+    let start = bytes.len();
+    payload(bytes);
+    let len = (bytes.len() - start) as u16;
+    bytes[start - 2..start].copy_from_slice(&len.to_le_bytes());
+}
+
+fn read_u32(payload: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(payload.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(payload: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(payload.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{MapSize, MatchSetup, Mode};
+
+    fn save() -> Save {
+        let setup = MatchSetup { mode: Mode::Ai, map_size: MapSize::Small, emeralds: [3, 4], ..MatchSetup::new(41) };
+        let mut replay = Recording::with_setup(setup, REPLAY_CAPACITY);
+        (0..5000u32).for_each(|frame| replay.record([(frame / 3) as u8, (frame / 5) as u8]));
+        assert!(replay.truncated());
+
+        Save {
+            seed: 42,
+            settings: Settings { sound: false, debug_palette: true },
+            stats: Stats {
+                matches: 3,
+                seconds_played: 1234,
+            },
+            replay: Some(replay),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = save().encode();
+        assert!(bytes.len() <= DISK_SIZE);
+
+        let decoded = Save::decode(&bytes);
+        assert_eq!(decoded.seed, 42);
+        assert!(!decoded.settings.sound);
+        assert!(decoded.settings.debug_palette);
+        assert_eq!(decoded.stats.matches, 3);
+        assert_eq!(decoded.stats.seconds_played, 1234);
+        assert_eq!(decoded.replay.as_ref().map(|replay| replay.frames()), save().replay.map(|replay| replay.frames()));
+        assert_eq!(decoded.replay.map(|replay| replay.setup()), save().replay.map(|replay| replay.setup()));
+    }
+
+    #[test]
+    fn migrates_legacy_seed() {
+        let decoded = Save::decode(&7u64.to_le_bytes());
+        assert_eq!(decoded.seed, 7);
+        assert!(decoded.settings.sound);
+        assert!(!decoded.settings.debug_palette);
+        assert!(decoded.replay.is_none());
+    }
+
+    #[test]
+    fn corrupt_or_foreign_disks_give_defaults() {
+        let mut bytes = save().encode();
+        bytes[HEADER_SIZE + 4] ^= 1;
+        assert_eq!(Save::decode(&bytes).seed, 0);
+
+        let mut bytes = save().encode();
+        bytes[4] = VERSION + 1;
+        assert_eq!(Save::decode(&bytes).seed, 0);
+
+        assert_eq!(Save::decode(b"some other cart's data").seed, 0);
+        assert_eq!(Save::decode(&[]).seed, 0);
+    }
+
+    #[test]
+    fn skips_unknown_sections() {
+        let mut bytes = save().encode();
+        section(&mut bytes, 200, |bytes| bytes.extend_from_slice(b"from the future"));
+        let checksum = Save::checksum(&bytes[HEADER_SIZE..]);
+        bytes[5..9].copy_from_slice(&checksum.to_le_bytes());
+
+        assert_eq!(Save::decode(&bytes).seed, 42);
+    }
+}
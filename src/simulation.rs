@@ -0,0 +1,2889 @@
+use crate::flowfield::FlowFields;
+use crate::pathfinding::{self, Goal};
+use crate::*;
+use std::hash::{Hash, Hasher};
+
+// Where the emeralds of the illagers come from. Villagers make theirs by farming
+#[derive(Clone, Copy, Hash)]
+pub struct IllagerIncome {
+    // emeralds given every interval (in frames) no matter what
+    pub passive_interval: u16,
+    pub passive: u8,
+
+    // for every villager killed and golem broken
+    pub kill_bounty: u8,
+    pub golem_bounty: u8,
+
+    // for every house set on fire and church blown up
+    pub house_loot: u8,
+    pub church_loot: u8,
+
+    // bonus given every raid wave, 0 frames to turn the waves off
+    pub raid_wave_interval: u32,
+    pub raid_wave_bonus: u8,
+}
+
+impl Default for IllagerIncome {
+    fn default() -> Self {
+        Self {
+            passive_interval: PASSIVE_INCOME_INTERVAL,
+            passive: PASSIVE_INCOME,
+            kill_bounty: KILL_BOUNTY,
+            golem_bounty: GOLEM_BOUNTY,
+            house_loot: HOUSE_LOOT,
+            church_loot: CHURCH_LOOT,
+            raid_wave_interval: RAID_WAVE_INTERVAL,
+            raid_wave_bonus: RAID_WAVE_BONUS,
+        }
+    }
+}
+
+// Who the illagers are played by
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Mode {
+    Ai,
+    Local,
+    Netplay,
+}
+
+// How much of the board can be played on, the rest of it is rocks
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MapSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl MapSize {
+    // Cells of rocks on each side of the map
+    pub fn margin(&self) -> u8 {
+        match self {
+            MapSize::Small => 6,
+            MapSize::Medium => 3,
+            MapSize::Large => 0,
+        }
+    }
+}
+
+// Everything picked on the setup screen, enough to start the same match again
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MatchSetup {
+    pub mode: Mode,
+    pub seed: u64,
+    pub map_size: MapSize,
+    pub emeralds: [u8; 2],
+}
+
+impl MatchSetup {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            mode: Mode::Local,
+            seed,
+            map_size: MapSize::Large,
+            emeralds: STARTING_EMERALDS[1],
+        }
+    }
+
+    // Size once encoded, see `encode`
+    pub const ENCODED_SIZE: usize = 4;
+
+    // Everything but the seed: mode, map size and both starting emeralds
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.mode as u8, self.map_size as u8, self.emeralds[0], self.emeralds[1]]);
+    }
+
+    // Missing or unknown bytes keep the defaults
+    pub fn decode(seed: u64, bytes: &[u8]) -> Self {
+        let mut setup = Self::new(seed);
+        setup.mode = match bytes.first() {
+            Some(0) => Mode::Ai,
+            Some(2) => Mode::Netplay,
+            _ => Mode::Local,
+        };
+        setup.map_size = match bytes.get(1) {
+            Some(0) => MapSize::Small,
+            Some(1) => MapSize::Medium,
+            _ => MapSize::Large,
+        };
+        if let Some(emeralds) = bytes.get(2..4) {
+            setup.emeralds = [emeralds[0], emeralds[1]];
+        }
+        setup
+    }
+}
+
+// Ways a match can end
+#[derive(Clone, Copy, Hash)]
+pub struct VictoryConditions {
+    // illagers win once there were churches (or houses) and none of them is left standing
+    pub churches_destroyed: bool,
+    pub houses_destroyed: bool,
+
+    // villagers win by holding on for this many frames (0 for no timer), or by killing every illager
+    // when they can't pay for a new one (or don't send one in before ELIMINATION_GRACE is over)
+    pub survival_frames: u32,
+    pub illagers_eliminated: bool,
+}
+
+impl Default for VictoryConditions {
+    fn default() -> Self {
+        Self {
+            churches_destroyed: true,
+            houses_destroyed: true,
+            survival_frames: SURVIVAL_TIME,
+            illagers_eliminated: true,
+        }
+    }
+}
+
+// How a match ended
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Outcome {
+    ChurchesDestroyed,
+    HousesDestroyed,
+    Survived,
+    IllagersEliminated,
+}
+
+impl Outcome {
+    // Index of the player that won (villagers are 0)
+    pub fn winner(&self) -> usize {
+        match self {
+            Outcome::ChurchesDestroyed | Outcome::HousesDestroyed => 1,
+            Outcome::Survived | Outcome::IllagersEliminated => 0,
+        }
+    }
+}
+
+// What happened during a match, per player (villagers first) when it makes sense
+#[derive(Clone, Copy, Default, Hash)]
+pub struct MatchStats {
+    pub spawned: [u16; 2],
+    pub lost: [u16; 2],
+    pub emeralds_earned: [u16; 2],
+
+    // houses and churches
+    pub buildings_burned: u16,
+}
+
+// Everything that makes up the state of a match. Advanced one frame at a time by `step`, using
+// nothing but the inputs of both players and its own RNG, so the same seed and input stream
+// always give the same state (on wasm32 or on the host)
+#[derive(Clone)]
+pub struct Simulation {
+    pub(crate) seed: u64,
+    pub(crate) rng: fastrand::Rng,
+
+    // the illagers are played by `ai`, and only the cells `margin` away from the edges can be played on
+    pub(crate) ai: bool,
+    pub(crate) margin: u8,
+    pub(crate) emeralds: [u8; 2],
+    pub(crate) income: IllagerIncome,
+    pub(crate) victory: VictoryConditions,
+    pub(crate) tick: u8,
+
+    // frames since the start of the match
+    pub(crate) frame: u32,
+    pub(crate) cursors: [u16; 2],
+    pub(crate) old_gamepad: [u8; 2],
+    pub(crate) new_gamepad: [u8; 2],
+    pub(crate) cursor_timer: [u8; 2],
+    pub(crate) view_local_cameras: [(u8, u8); 2],
+    pub(crate) button_held: [bool; 2],
+    pub(crate) action_possible: [bool; 2],
+    pub(crate) particles: Vec<Particle>,
+    pub(crate) entities: Vec<Entity>,
+    pub(crate) projectiles: Vec<Projectile>,
+
+    pub(crate) current_selected_class: [u8; 2],
+    pub(crate) grid: Board,
+
+    // frames until the actions 3..=5 of each player can be used again
+    pub(crate) action_cooldowns: [[u16; 3]; 2],
+
+    // cell idle units of each clan gather at
+    pub(crate) rally: [Option<u16>; 2],
+
+    // set once the match is over, nothing moves anymore after that
+    pub(crate) outcome: Option<Outcome>,
+    pub(crate) stats: MatchStats,
+
+    // frames since the last illager died with nobody to replace them
+    pub(crate) eliminated_for: u16,
+
+    // frames until the all-clear after the bell rang, and the villagers hiding in the meantime
+    // (with a cell of the building they're in)
+    pub(crate) alarm: u16,
+    pub(crate) sheltered: Vec<(u16, Entity)>,
+
+    // cached navigation toward the objectives of the units
+    pub(crate) flow_fields: FlowFields,
+
+    // sounds requested during the last step, played by whoever owns the simulation
+    pub(crate) sounds: Vec<Noise>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Self {
+        Self::with_setup(&MatchSetup::new(seed))
+    }
+
+    pub fn with_setup(setup: &MatchSetup) -> Self {
+        let MatchSetup { mode, seed, map_size, emeralds } = *setup;
+        let margin = map_size.margin();
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let grid = terrain::generate(&mut rng, margin);
+
+        // the villager camera always starts at the middle of the map (village)
+        let (mid_x, mid_y) = (GRID_SIZE_X / 2, GRID_SIZE_Y / 2);
+        let village_cursor = grid_from_vec(mid_x, mid_y);
+
+        Self {
+            seed,
+            rng,
+            ai: mode == Mode::Ai,
+            margin,
+            emeralds,
+            income: IllagerIncome::default(),
+            victory: VictoryConditions::default(),
+            tick: 0,
+            frame: 0,
+            cursors: [village_cursor, grid_from_vec(margin, margin)],
+            button_held: [false, false],
+            action_possible: [false, false],
+            new_gamepad: [0; 2],
+            old_gamepad: [0; 2],
+            cursor_timer: [0, 0],
+            current_selected_class: [0, 0],
+            action_cooldowns: [[0; 3]; 2],
+            rally: [None, None],
+            outcome: None,
+            stats: MatchStats::default(),
+            eliminated_for: 0,
+            alarm: 0,
+            sheltered: Vec::new(),
+            particles: Vec::new(),
+            grid,
+            flow_fields: FlowFields::default(),
+            entities: Vec::new(),
+            projectiles: Vec::new(),
+            view_local_cameras: [(mid_x - GRID_LOCAL_SIZE_X / 2, mid_y - GRID_LOCAL_SIZE_Y / 2), (0, 0)],
+            sounds: Vec::new(),
+        }
+    }
+
+    // Starts a new match in place of this one. The board and the buffers get reused, so the cart never has
+    // to fit two simulations on its heap at once
+    pub fn reset(&mut self, setup: &MatchSetup) {
+        let MatchSetup { mode, seed, map_size, emeralds } = *setup;
+        let margin = map_size.margin();
+        self.flow_fields = FlowFields::default();
+        self.particles.clear();
+        self.entities.clear();
+        self.projectiles.clear();
+        self.sheltered.clear();
+        self.sounds.clear();
+
+        self.rng = fastrand::Rng::with_seed(seed);
+        terrain::generate_into(&mut self.grid, &mut self.rng, margin);
+
+        let (mid_x, mid_y) = (GRID_SIZE_X / 2, GRID_SIZE_Y / 2);
+        self.seed = seed;
+        self.ai = mode == Mode::Ai;
+        self.margin = margin;
+        self.emeralds = emeralds;
+        self.income = IllagerIncome::default();
+        self.victory = VictoryConditions::default();
+        self.tick = 0;
+        self.frame = 0;
+        self.cursors = [grid_from_vec(mid_x, mid_y), grid_from_vec(margin, margin)];
+        self.button_held = [false, false];
+        self.action_possible = [false, false];
+        self.new_gamepad = [0; 2];
+        self.old_gamepad = [0; 2];
+        self.cursor_timer = [0, 0];
+        self.current_selected_class = [0, 0];
+        self.action_cooldowns = [[0; 3]; 2];
+        self.rally = [None, None];
+        self.outcome = None;
+        self.stats = MatchStats::default();
+        self.eliminated_for = 0;
+        self.alarm = 0;
+        self.view_local_cameras = [(mid_x - GRID_LOCAL_SIZE_X / 2, mid_y - GRID_LOCAL_SIZE_Y / 2), (0, 0)];
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn tick(&self) -> u8 {
+        self.tick
+    }
+
+    // Sounds requested by the last step
+    pub fn sounds(&self) -> &[Noise] {
+        &self.sounds
+    }
+
+    // How the match ended, if it did
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    pub fn stats(&self) -> &MatchStats {
+        &self.stats
+    }
+
+    // Advance the match by one frame given the gamepads of both players (p1 first)
+    // Finished matches stay frozen
+    pub fn step(&mut self, inputs: [u8; 2]) {
+        self.sounds.clear();
+        if self.outcome.is_some() {
+            return;
+        }
+
+        self.update();
+
+        if self.tick.is_multiple_of(FRAMES_PER_PARTICLE_TICK) {
+            self.update_particles();
+        }
+
+        for cooldown in self.action_cooldowns.iter_mut().flatten() {
+            *cooldown = cooldown.saturating_sub(1);
+        }
+
+        // the AI moves its cursor around before anything gets checked
+        let mut inputs = inputs;
+        if self.ai {
+            inputs[1] = self.ai_inputs();
+        }
+
+        self.action_possible();
+        self.handle_input(inputs);
+
+        self.tick += 1;
+        self.tick %= 60;
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    // Stable hash of the whole state, to check that two simulations did not diverge
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = Fnv::default();
+        self.rng.get_seed().hash(&mut hasher);
+        self.ai.hash(&mut hasher);
+        self.margin.hash(&mut hasher);
+        self.emeralds.hash(&mut hasher);
+        self.income.hash(&mut hasher);
+        self.tick.hash(&mut hasher);
+        self.frame.hash(&mut hasher);
+        self.cursors.hash(&mut hasher);
+        self.old_gamepad.hash(&mut hasher);
+        self.cursor_timer.hash(&mut hasher);
+        self.view_local_cameras.hash(&mut hasher);
+        self.current_selected_class.hash(&mut hasher);
+        self.action_cooldowns.hash(&mut hasher);
+        self.rally.hash(&mut hasher);
+        self.outcome.hash(&mut hasher);
+        self.stats.hash(&mut hasher);
+        self.eliminated_for.hash(&mut hasher);
+        self.alarm.hash(&mut hasher);
+        self.sheltered.hash(&mut hasher);
+        self.grid[..].hash(&mut hasher);
+        self.entities.hash(&mut hasher);
+        self.projectiles.hash(&mut hasher);
+        self.particles.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    //
+    //
+    // "SUMMON" TYPE ACTIONS: 0..=2
+    // they summon:
+    //    index       |     0       |    1     |    2
+    // villager clan  |  villager   | farmer   | smith      
+    // illager clan   |  vindicator | pillager | evoker     
+    //
+    //
+    fn action_possible(&mut self) {
+        // === Villagers ===
+        // 1, 2, 3 & on house? true else false
+        // 4 (rally) on empty? true else false
+        // 5 (barricade) on empty & nobody standing there? true else false
+        // 6 (ring the bell) on the bell? true else false
+        // === Illagers ===
+        // 1, 2, 3 & on empty border ? true else false
+        // 4 (rally) on empty? true else false
+        // 5 (torch) on something flammable & illager close by? true else false
+        // 6 (reinforcements) on empty border? true else false
+        // everything also needs the emeralds for it (and 4, 5, 6 to be done cooling down)
+
+
+        // If we are controlling Villagers, we want to be able to summon villagers when doing the "Action" when we have a building selected
+        fn can_we_spawn_villagers(cell: &CellState, cursor: u16) -> bool {
+            matches!(*cell, CellState::House(..) | CellState::Church(..) | CellState::House2(..))
+        }
+
+        // If we are controlling Illagers, we want to be able to summon them at the border of the map, and on empty cells as well
+        let margin = self.margin;
+        let can_we_spawn_illagers = |cell: &CellState, cursor: u16| matches!(*cell, CellState::Empty) && at_border(cursor, margin);
+        
+        for player_index in 0..2 {
+            // calculate the cursor position and cell of the current player
+            let cursor = self.cursors[player_index];
+            let cell = &self.grid[cursor as usize];
+
+            self.action_possible[player_index] = match self.current_selected_class[player_index] {
+                // actions 0..=2 are the "summon type actions"
+                // they summon:
+                //    index       |     0       |    1     |    2
+                // villager clan  |  villager   | farmer   | smith      
+                // illager clan   |  vindicator | pillager | evoker     
+                0..=2 => {
+                    // check if player has enough currency (them emmies)
+                    let price = PRICES[self.current_selected_class[player_index] as usize + 3 * player_index];
+                    let player_has_enough_currency = self.emeralds[player_index] >= price;
+
+                    // check if the player can do the specified action
+                    let player_can_do_thing = if player_index == 0 {
+                        can_we_spawn_villagers(cell, cursor)
+                    } else  {
+                        can_we_spawn_illagers(cell, cursor)
+                    };
+
+                    // check if we can do both...
+                    player_has_enough_currency && player_can_do_thing
+                }
+
+                // actions 3..=5 have a price and a cooldown on top of their own rules
+                //    index       |    3    |     4     |      5
+                // villager clan  |  rally  | barricade | ring the bell
+                // illager clan   |  rally  |   torch   | reinforcements
+                action @ 3..=5 => {
+                    let action = action as usize - 3;
+                    let ready = self.action_cooldowns[player_index][action] == 0
+                        && self.emeralds[player_index] >= ACTION_PRICES[action + 3 * player_index];
+
+                    // picking units and ordering them around is free, only placing the flag isn't
+                    let commanding = action == 0
+                        && (self.entities.iter().any(|entity| entity.selected && entity.is_illager() == (player_index == 1))
+                            || self.units_near(player_index, cursor).next().is_some());
+
+                    let valid = match (player_index, action) {
+                        // anywhere units can stand
+                        (_, 0) => matches!(*cell, CellState::Empty),
+
+                        // not right on top of someone
+                        (0, 1) => matches!(*cell, CellState::Empty) && !self.entities.iter().any(|entity| center_cell(entity.position_x, entity.position_y) == cursor),
+                        (0, _) => matches!(*cell, CellState::Bell(BuildingState::Solid, _)),
+
+                        // something that burns, close enough to one of ours
+                        (_, 1) => {
+                            let (x, y) = vec_from_grid(cursor);
+                            flammable(cell) && self.entities.iter().any(|entity| {
+                                entity.alive() && entity.is_illager() && entity.distance_to(x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16) <= TORCH_RANGE
+                            })
+                        }
+                        (_, _) => can_we_spawn_illagers(cell, cursor),
+                    };
+
+                    commanding || (ready && valid)
+                }
+
+                _ => unreachable!()
+            };
+        }
+    }
+
+    // Handle the gamepad input of both players (p1 first). Only supports 2 players
+    // Also moves the appropriate selectors (and current player view if needed)
+    fn handle_input(&mut self, inputs: [u8; 2]) {
+        // Moves the cursor, also moving the view local camera when it goes out of bounds
+        // The cursor stays on the playable part of the map (margin cells away from the edges)
+        fn move_cursor(step_x: i8, step_y: i8, margin: u8, cursor: &mut u16, camera: &mut (i8, i8)) {
+            let mut x = (*cursor % GRID_SIZE_X as u16) as i8;
+            let mut y = (*cursor / GRID_SIZE_X as u16) as i8;
+            let margin = margin as i8;
+
+            // Works ig
+            x += (x + step_x).clamp(margin, GRID_SIZE_X as i8 - 1 - margin) - x;
+            y += (y + step_y).clamp(margin, GRID_SIZE_Y as i8 - 1 - margin) - y;
+            let x = x as u8;
+            let y = y as u8;
+
+
+            fn uncontained_clamp(coord: &mut i8, val: i8, max: u8) {                
+                // local view min and max
+                let min = *coord;
+                let max = *coord + (max - 1) as i8;
+                
+                // max if x > max
+                // min if x < min
+                // val otherwise (to make subtraction 0)
+                let diff = if val < min {
+                    min
+                } else if val > max {
+                    max
+                } else {
+                    val
+                };
+
+                // move camera coordinate by difference
+                *coord += val - diff;
+            }
+
+            // Completely refactored camera movement code that will simply try to accodomate keeping the cursor in the 16x12 grid
+            // by taking cur diff to local edges
+            uncontained_clamp(&mut camera.0, x as i8, GRID_LOCAL_SIZE_X);
+            uncontained_clamp(&mut camera.1, y as i8, GRID_LOCAL_SIZE_Y);
+
+            *cursor = y as u16 * GRID_SIZE_X as u16 + x as u16;
+        }
+
+        for player_index in 0..2 {
+            let last = self.old_gamepad[player_index];
+            let current = inputs[player_index];
+            let new = current & (last ^ current);
+            self.old_gamepad[player_index] = current;
+            self.new_gamepad[player_index] = new;
+
+            // Move cursor on grid
+            let grid_pos: &mut u16 = &mut self.cursors[player_index];
+            let camera = &mut self.view_local_cameras[player_index];
+            let tick: &mut u8 = &mut self.cursor_timer[player_index];
+            let cursor_tick_check: bool = tick.is_multiple_of(CURSOR_MOVEMENT_SPEED_INV);
+            *tick = tick.wrapping_add(1);
+
+            let mut step_left: i8 = -1;
+            let mut step_right: i8 = 1;
+            let mut step_down: i8 = 1;
+            let mut step_up: i8 = -1;
+
+            match &self.grid[*grid_pos as usize] {
+                CellState::House(_x, y, _) | CellState::House2(_x, y, _) => {
+                    if y % 2 == 0 {
+                        step_right *= 2
+                    } else {
+                        step_left *= 2
+                    };
+                    if y > &1 {
+                        step_up *= 2
+                    } else {
+                        step_down *= 2
+                    };
+                }
+
+                CellState::Church(_x, y, _) => {
+                    if y % 2 == 0 {
+                        step_right *= 2
+                    } else {
+                        step_left *= 2
+                    };
+                    if y > &3 {
+                        step_up *= 3
+                    } else if y > &1 {
+                        step_up *= 2;
+                        step_down *= 2
+                    } else {
+                        step_down *= 3
+                    };
+                }
+
+                CellState::BigRock(y) | CellState::Tree(y, _) | CellState::Stand(y, _) => {
+                    if y % 2 == 0 {
+                        step_right *= 2
+                    } else {
+                        step_left *= 2
+                    };
+                    if y > &1 {
+                        step_up *= 2
+                    } else {
+                        step_down *= 2
+                    };
+                }
+
+                CellState::Lamppost(y) => {
+                    if y == &1 {
+                        step_up *= 2
+                    } else {
+                        step_down *= 2
+                    };
+                }
+
+                CellState::Farm(y, ..) | CellState::Hay(y, _) => {
+                    if y % 2 == 0 {
+                        step_right *= 2
+                    } else {
+                        step_left *= 2
+                    };
+                }
+
+                _ => { }
+            }
+
+            if cursor_tick_check && current != 0 {
+                let (x, y) = if current & BUTTON_UP != 0 {
+                    (0, step_up)
+                } else if current & BUTTON_DOWN != 0 {
+                    (0, step_down)
+                } else if current & BUTTON_LEFT != 0 {
+                    (step_left, 0)
+                } else if current & BUTTON_RIGHT != 0 {
+                    (step_right, 0)
+                } else {
+                    (0, 0)
+                };
+
+                let mut camera_temp = (camera.0 as i8, camera.1 as i8);
+                move_cursor(x, y, self.margin, grid_pos, &mut camera_temp);
+                camera.0 = camera_temp.0 as u8;
+                camera.1 = camera_temp.1 as u8;
+            }
+            
+            if current == 0 {
+                *tick = 0;
+            }
+
+            let selected = &mut self.current_selected_class[player_index];
+
+            // Cycle current selected class
+            if new & BUTTON_2 != 0 {
+                *selected += 1;
+                *selected %= 6;
+                self.sounds.push(Noise::TungTungTungSahour);
+
+                // switching away drops whatever group was picked
+                for entity in self.entities.iter_mut().filter(|entity| entity.is_illager() == (player_index == 1)) {
+                    entity.selected = false;
+                }
+            }
+
+            // Place currently selected class
+            // Action possible permits this to happen,
+            // This is determined somewhere else (TBD)
+            if new & BUTTON_1 != 0 && self.action_possible[player_index] {
+                let points: &mut u8 = &mut self.emeralds[player_index];
+
+                // make sure the cell is empty so we can place our shit there
+                // this needs to be redone as villagers have to be placed by selecting a house and will come out of the bottom
+                if *selected < 3 {
+                    // checked sub to make sure we don't cause a crash (also saves us from
+                    // manually comparing to check if we have enough points to spend)
+                    if let Some(new_points) =
+                        points.checked_sub(PRICES[*selected as usize + 3 * player_index])
+                    {
+                        // logic that handles setting new classes
+                        // this makes things so much easier lol nice
+                        // `cell` is the cell index position of a cell in which we can spawn illager / villagers
+                        let cell: Option<u16> = if player_index == 1 && matches!(self.grid[*grid_pos as usize], CellState::Empty) { 
+                            Some(*grid_pos)
+                        } else {
+                            // pick a plausible spawning position on the outline of the building
+                            let random_position_building_outline: Option<u16> = match self.grid[*grid_pos as usize] {
+                                CellState::Church(BuildingState::Solid, j, _) => pick_random_location_building_outline(
+                                    &self.grid,
+                                    2,
+                                    3,
+                                    j, 
+                                    *grid_pos,
+                                    &mut self.rng,
+                                ),
+
+                                CellState::House(BuildingState::Solid, j, _) | CellState::House2(BuildingState::Solid, j, _) => pick_random_location_building_outline(
+                                    &self.grid,
+                                    2,
+                                    2,
+                                    j, 
+                                    *grid_pos,
+                                    &mut self.rng,
+                                ),
+
+                                _ => None
+                            };
+
+                            random_position_building_outline
+                        };
+
+                        // "player_index" is player index (where 0 is villager and 1 is illager)
+                        // "selected" is the selected class index (0..3)
+                        if let Some(plausible_cell_index) = cell { 
+                            let (x, y) = vec_from_grid(plausible_cell_index);
+
+                            *points = new_points;
+                            let entity_type = match (player_index, selected) {
+                                // villager clan classes
+                                (0, 0) => EntityType::VillagerClan(VillagerClan::Villager),
+                                (0, 1) => EntityType::VillagerClan(VillagerClan::Farmer(0)),
+                                (0, 2) => EntityType::VillagerClan(VillagerClan::Smith(0)),
+
+                                // illager clan classes
+                                (1, 0) => EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle),
+                                (1, 1) => EntityType::IllagerClan(IllagerClan::Pillager, IllagerState::Idle),
+                                (1, 2) => EntityType::IllagerClan(IllagerClan::Evoker(0), IllagerState::Idle), 
+
+                                _ => unreachable!()
+                            };
+
+                            // spawn the new entity at the given cell position
+                            let global_pos_x = x as u16 * CELL_SIZE as u16;
+                            let global_pos_y = y as u16 * CELL_SIZE as u16;
+                            let entity = Entity::new(entity_type, global_pos_x, global_pos_y);
+
+                            self.spawn(entity);
+                            
+                            self.sounds.push(Noise::Ting);
+                        } else {
+                            self.sounds.push(Noise::SixSeven);
+                        }
+                    }
+                } else {
+                    let action = *selected as usize - 3;
+                    self.perform_action(player_index, action);
+                }
+            }
+
+            self.button_held[player_index] = current & BUTTON_1 != 0;
+        }
+    }
+
+    // Does one of the actions 3..=5 at the cursor, action_possible already made sure we can
+    fn perform_action(&mut self, player_index: usize, action: usize) {
+        if action == 0 && self.command(player_index) {
+            return;
+        }
+
+        let slot = action + 3 * player_index;
+        self.emeralds[player_index] -= ACTION_PRICES[slot];
+        self.action_cooldowns[player_index][action] = ACTION_COOLDOWNS[slot];
+        let cursor = self.cursors[player_index];
+
+        match (player_index, action) {
+            (_, 0) => {
+                self.rally[player_index] = Some(cursor);
+
+                // illagers drop whatever landmark they were marching on
+                if player_index == 1 {
+                    self.entities.iter_mut().filter(|entity| entity.is_illager()).for_each(|entity| entity.goal = None);
+                }
+                self.sounds.push(Noise::Ting);
+            }
+
+            (0, 1) => {
+                self.grid[cursor as usize] = CellState::Barricade(0);
+                self.sounds.push(Noise::Anvil);
+            }
+
+            // golems and smiths drop everything and run to the bell, everyone else goes into hiding
+            (0, _) => {
+                for entity in self.entities.iter_mut().filter(|entity| matches!(entity.entity_type, EntityType::VillagerClan(VillagerClan::Golem(..) | VillagerClan::Smith(_)))) {
+                    entity.goal = Some(Goal::Building(cursor));
+                }
+                self.alarm = ALARM_DURATION;
+                self.sounds.push(Noise::Bell);
+            }
+
+            (_, 1) => self.ignite(cursor),
+
+            (_, _) => {
+                let (x, y) = vec_from_grid(cursor);
+                for _ in 0..REINFORCEMENT_COUNT {
+                    let entity_type = EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle);
+                    self.spawn(Entity::new(entity_type, x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16));
+                }
+                self.sounds.push(Noise::Raid);
+            }
+        }
+    }
+
+    // Picks the units of the player around the cursor, or sends the ones already picked to it
+    // Returns false if there's nobody to pick or to order around, so the rally flag goes there instead
+    fn command(&mut self, player_index: usize) -> bool {
+        let cursor = self.cursors[player_index];
+        let illagers = player_index == 1;
+        if !self.entities.iter().any(|entity| entity.selected && entity.is_illager() == illagers) {
+            let picked: Vec<usize> = self.units_near(player_index, cursor).collect();
+            for index in picked.iter() {
+                self.entities[*index].selected = true;
+            }
+
+            if !picked.is_empty() {
+                self.sounds.push(Noise::Ting);
+            }
+            return !picked.is_empty();
+        }
+
+        // go after whoever is around the cursor (or the building under it for illagers), otherwise just go there
+        let (x, y) = vec_from_grid(cursor);
+        let (x, y) = (x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16);
+        let enemy = self.entities.iter()
+            .find(|entity| entity.alive() && entity.is_illager() != illagers && entity.distance_to(x, y) <= COMMAND_RADIUS)
+            .map(|entity| center_cell(entity.position_x, entity.position_y));
+
+        let order = match (enemy, self.grid[cursor as usize]) {
+            (Some(cell), _) => Order::Attack(Goal::Cell(cell)),
+            (None, CellState::Empty) => Order::Move(Goal::Cell(cursor)),
+            (None, building) if illagers && building_integrity(&building).is_some() => Order::Attack(Goal::Building(cursor)),
+            (None, _) => Order::Move(Goal::Building(cursor)),
+        };
+
+        for entity in self.entities.iter_mut().filter(|entity| entity.selected && entity.is_illager() == illagers) {
+            entity.selected = false;
+            entity.order = Some(order);
+        }
+        self.sounds.push(Noise::Ting);
+        true
+    }
+
+    // Units of a player (alive ones) close enough to the cursor to be picked
+    fn units_near(&self, player_index: usize, cursor: u16) -> impl Iterator<Item = usize> + '_ {
+        let (x, y) = vec_from_grid(cursor);
+        let (x, y) = (x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16);
+        self.entities.iter().enumerate()
+            .filter(move |(_, entity)| entity.alive() && entity.is_illager() == (player_index == 1) && entity.distance_to(x, y) <= COMMAND_RADIUS)
+            .map(|(index, _)| index)
+    }
+
+    // Called every frame
+    fn update(&mut self) {
+        // farms grow in place, no need for a copy of the board
+        if self.tick.is_multiple_of(FARM_GROWTH_INTERVAL) {
+            for cell in self.grid.iter_mut() {
+                if let CellState::Farm(j, growth @ ..FARM_RIPE, 0) = *cell {
+                    *cell = CellState::Farm(j, growth + 1, 0);
+                }
+            }
+        }
+        self.flow_fields.invalidate_if_changed(&self.grid);
+
+        self.update_fires();
+        self.update_targets();
+        self.update_smiths();
+        self.update_firefighting();
+        self.update_farming();
+        self.update_shelters();
+        self.update_spells();
+        self.update_entities();
+        self.update_combat();
+        self.update_projectiles();
+        self.update_income();
+        self.check_victory();
+    }
+
+    // Adds a new unit to the match
+    fn spawn(&mut self, entity: Entity) {
+        // someone new for the illagers restarts their grace period
+        if entity.is_illager() {
+            self.eliminated_for = 0;
+        }
+
+        self.stats.spawned[entity.is_illager() as usize] += 1;
+        self.entities.push(entity);
+    }
+
+    // Ends the match as soon as one of the victory conditions is met
+    fn check_victory(&mut self) {
+        let VictoryConditions { churches_destroyed, houses_destroyed, survival_frames, illagers_eliminated } = self.victory;
+        let (mut churches, mut churches_standing, mut houses, mut houses_standing) = (false, false, false, false);
+        for cell in self.grid.iter() {
+            match cell {
+                CellState::Church(state, ..) => {
+                    churches = true;
+                    churches_standing |= !matches!(state, BuildingState::Destroyed);
+                }
+
+                CellState::House(state, ..) | CellState::House2(state, ..) => {
+                    houses = true;
+                    houses_standing |= !matches!(state, BuildingState::Destroyed);
+                }
+
+                _ => {}
+            }
+        }
+
+        // passive income would always pay for someone new eventually, so they only get a grace period
+        // (the clock starts once the illagers lost someone, not before they even showed up)
+        let broke = self.emeralds[1] < PRICES[3..].iter().copied().min().unwrap_or(0);
+        let illagers_left = self.entities.iter().any(|entity| entity.alive() && entity.is_illager());
+        if illagers_left || self.stats.lost[1] == 0 {
+            self.eliminated_for = 0;
+        } else {
+            self.eliminated_for = self.eliminated_for.saturating_add(1);
+        }
+        let eliminated = self.eliminated_for > 0 && (broke || self.eliminated_for > ELIMINATION_GRACE);
+        self.outcome = if churches_destroyed && churches && !churches_standing {
+            Some(Outcome::ChurchesDestroyed)
+        } else if houses_destroyed && houses && !houses_standing {
+            Some(Outcome::HousesDestroyed)
+        } else if survival_frames > 0 && self.frame >= survival_frames {
+            Some(Outcome::Survived)
+        } else if illagers_eliminated && eliminated {
+            Some(Outcome::IllagersEliminated)
+        } else {
+            None
+        };
+
+        if let Some(outcome) = self.outcome {
+            self.sounds.push(if outcome.winner() == 1 { Noise::Raid } else { Noise::Bell });
+        }
+    }
+
+    // Frames the villagers still have to hold on for, if there's a timer
+    pub(crate) fn survival_left(&self) -> Option<u32> {
+        let frames = self.victory.survival_frames;
+        (frames > 0).then(|| frames.saturating_sub(self.frame))
+    }
+
+    // Vindicators, pillagers and vex go after the closest villager or golem they can see, and golems
+    // after the closest illager. Pillagers with nobody around shoot at the houses in range instead
+    fn update_targets(&mut self) {
+        for index in 0..self.entities.len() {
+            let hunter = &self.entities[index];
+            let sight = match hunter.entity_type {
+                EntityType::IllagerClan(IllagerClan::Vindicator, _) => VINDICATOR_SIGHT,
+                EntityType::IllagerClan(IllagerClan::Pillager, _) => PILLAGER_SIGHT,
+                EntityType::IllagerClan(IllagerClan::Vex(_), _) => VEX_SIGHT,
+                EntityType::VillagerClan(VillagerClan::Golem(..)) => GOLEM_SIGHT,
+                _ => continue,
+            };
+
+            if !hunter.alive() {
+                continue;
+            }
+
+            let mut target = self.entities.iter()
+                .filter(|other| other.alive() && other.is_illager() != hunter.is_illager())
+                .map(|other| (other.position_x, other.position_y, hunter.distance_to(other.position_x, other.position_y)))
+                .filter(|(_, _, distance)| *distance <= sight)
+                .min_by_key(|(_, _, distance)| *distance)
+                .map(|(x, y, _)| (x, y));
+
+            if target.is_none() && matches!(hunter.entity_type, EntityType::IllagerClan(IllagerClan::Pillager, _)) {
+                target = self.flammable_in_reach(hunter);
+            }
+
+            self.entities[index].target = target;
+        }
+    }
+
+    // Smiths repair the broken golems they can see, and otherwise work on a new golem at their workshop
+    fn update_smiths(&mut self) {
+        let working = self.tick.is_multiple_of(SMITH_WORK_INTERVAL);
+        let workshops: Vec<u16> = self.grid.iter().enumerate()
+            .filter(|(_, cell)| matches!(cell, CellState::Bell(..) | CellState::Stand(0, _)))
+            .map(|(index, _)| index as u16)
+            .collect();
+
+        for index in 0..self.entities.len() {
+            let smith = &self.entities[index];
+            if !smith.alive() || !matches!(smith.entity_type, EntityType::VillagerClan(VillagerClan::Smith(_))) {
+                continue;
+            }
+
+            // broken golems don't move, so their position is enough to find them again
+            let broken = self.entities.iter()
+                .filter(|other| matches!(other.entity_type, EntityType::VillagerClan(VillagerClan::Golem(_, GolemState::Broken))))
+                .map(|other| (other.position_x, other.position_y))
+                .filter(|(x, y)| smith.distance_to(*x, *y) <= SMITH_SIGHT)
+                .min_by_key(|(x, y)| smith.distance_to(*x, *y));
+
+            self.entities[index].target = broken;
+            let smith = &self.entities[index];
+            if let Some((x, y)) = broken {
+                if working && smith.engaged() {
+                    self.repair_golem(x, y);
+                }
+                continue;
+            }
+
+            // then the ruins around, as long as the village can pay for the ones nobody started on
+            let cell = center_cell(smith.position_x, smith.position_y);
+            let affordable = self.emeralds[0] >= REBUILD_COST;
+            let ruin = move |cell: &CellState| matches!(building_integrity(cell), Some((BuildingState::Destroyed, integrity)) if integrity > 0 || affordable);
+            if self.find_work_spot(index, SMITH_SIGHT, ruin) {
+                if let Some(building) = self.adjacent_building(cell, ruin).filter(|_| working && self.entities[index].goal == Some(Goal::Cell(cell))) {
+                    self.rebuild(building);
+                }
+                continue;
+            }
+
+            // look for the closest workshop we can reach (once a second, like the illagers)
+            let smith = &self.entities[index];
+            if smith.goal.is_none() && self.tick == 0 {
+                self.entities[index].goal = self.closest_reachable(cell, &workshops);
+            }
+
+            let Some(goal) = self.entities[index].goal else {
+                continue;
+            };
+
+            let at_workshop = self.flow_fields.next_step(&self.grid, cell, goal) == Some(cell);
+            if !working || !at_workshop {
+                continue;
+            }
+
+            let smith = &mut self.entities[index];
+            let EntityType::VillagerClan(VillagerClan::Smith(work)) = &mut smith.entity_type else {
+                continue;
+            };
+
+            *work += 1;
+            if *work >= GOLEM_WORK {
+                *work = 0;
+                let golem = Entity::new(EntityType::VillagerClan(VillagerClan::Golem(0, GolemState::Idle)), smith.position_x, smith.position_y);
+                self.spawn(golem);
+                self.sounds.push(Noise::KaboomNoise);
+            } else {
+                self.sounds.push(Noise::Anvil);
+            }
+        }
+    }
+
+    // Closest of the given buildings we can walk to from a cell
+    fn closest_reachable(&mut self, cell: u16, buildings: &[u16]) -> Option<Goal> {
+        let mut buildings = buildings.to_vec();
+        buildings.sort_by_key(|building| pathfinding::distance(cell, *building));
+        buildings
+            .into_iter()
+            .map(Goal::Building)
+            .find(|goal| self.flow_fields.next_step(&self.grid, cell, *goal).is_some())
+    }
+
+    // Farmers harvest the ripe farms and sell the crops at the hay or a stand
+    // Illagers walking by the farms trample them
+    fn update_farming(&mut self) {
+        for entity in self.entities.iter().filter(|entity| entity.alive() && entity.is_illager()) {
+            if matches!(entity.entity_type, EntityType::IllagerClan(IllagerClan::Vex(_), _)) {
+                continue;
+            }
+
+            for cell in get_neighbours(center_cell(entity.position_x, entity.position_y)).into_iter().filter(|cell| *cell != u16::MAX) {
+                if let CellState::Farm(_, growth @ 1.., _) = &mut self.grid[cell as usize] {
+                    *growth = 0;
+                }
+            }
+        }
+
+        let mut ripe: Vec<u16> = self.grid.iter().enumerate()
+            .filter(|(_, cell)| matches!(cell, CellState::Farm(_, FARM_RIPE.., _)))
+            .map(|(index, _)| index as u16)
+            .collect();
+        let stores: Vec<u16> = self.grid.iter().enumerate()
+            .filter(|(_, cell)| matches!(cell, CellState::Hay(0, _) | CellState::Stand(0, _)))
+            .map(|(index, _)| index as u16)
+            .collect();
+
+        for index in 0..self.entities.len() {
+            let farmer = &self.entities[index];
+            let EntityType::VillagerClan(VillagerClan::Farmer(crops)) = farmer.entity_type else {
+                continue;
+            };
+
+            if !farmer.alive() {
+                continue;
+            }
+
+            // go for the closest ripe farm until our hands are full
+            let cell = center_cell(farmer.position_x, farmer.position_y);
+            let farm = ripe.iter().copied().min_by_key(|farm| pathfinding::distance(cell, *farm));
+            if let Some(farm) = farm.filter(|_| crops < FARMER_CAPACITY) {
+                let (x, y) = vec_from_grid(farm);
+                let farmer = &mut self.entities[index];
+                farmer.goal = None;
+                farmer.target = Some((x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16));
+
+                if farmer.engaged() {
+                    farmer.target = None;
+                    farmer.entity_type = EntityType::VillagerClan(VillagerClan::Farmer(crops + 1));
+                    if let CellState::Farm(_, growth, _) = &mut self.grid[farm as usize] {
+                        *growth = 0;
+                    }
+                    ripe.retain(|other| *other != farm);
+                    self.sounds.push(Noise::Harvest);
+                }
+                continue;
+            }
+
+            self.entities[index].target = None;
+            if crops == 0 {
+                self.entities[index].goal = None;
+                continue;
+            }
+
+            // off to sell what we have
+            if self.entities[index].goal.is_none() && self.tick == 0 {
+                self.entities[index].goal = self.closest_reachable(cell, &stores);
+            }
+
+            let Some(goal) = self.entities[index].goal else {
+                continue;
+            };
+
+            if self.flow_fields.next_step(&self.grid, cell, goal) == Some(cell) {
+                let farmer = &mut self.entities[index];
+                farmer.entity_type = EntityType::VillagerClan(VillagerClan::Farmer(0));
+                farmer.goal = None;
+                self.earn(0, crops * EMERALDS_PER_CROP);
+                self.sounds.push(Noise::Ting);
+            }
+        }
+    }
+
+    // Plain villagers run to the burning buildings they see and try to put them out
+    fn update_firefighting(&mut self) {
+        let working = self.tick.is_multiple_of(EXTINGUISH_INTERVAL);
+        let burning = |cell: &CellState| matches!(building_integrity(cell), Some((BuildingState::Burning, _)));
+
+        for index in 0..self.entities.len() {
+            let villager = &self.entities[index];
+            if !villager.alive() || !matches!(villager.entity_type, EntityType::VillagerClan(VillagerClan::Villager)) {
+                continue;
+            }
+
+            let cell = center_cell(villager.position_x, villager.position_y);
+            if !self.find_work_spot(index, VILLAGER_SIGHT, burning) || !working || self.entities[index].goal != Some(Goal::Cell(cell)) {
+                continue;
+            }
+
+            let Some(building) = self.adjacent_building(cell, burning) else {
+                continue;
+            };
+
+            self.sounds.push(Noise::Splash);
+            if self.rng.u8(..EXTINGUISH_CHANCE_INV) == 0 {
+                self.extinguish(building);
+            }
+        }
+    }
+
+    // Villagers and farmers hide in the closest standing house or church while the alarm lasts, and come
+    // back out once it's all clear (or as soon as their shelter gets hit)
+    fn update_shelters(&mut self) {
+        self.alarm = self.alarm.saturating_sub(1);
+        let safe = |cell: &CellState| matches!(cell, CellState::House(BuildingState::Solid, ..) | CellState::House2(BuildingState::Solid, ..) | CellState::Church(BuildingState::Solid, ..));
+
+        let alarm = self.alarm;
+        let (leaving, staying): (Vec<_>, Vec<_>) = std::mem::take(&mut self.sheltered)
+            .into_iter()
+            .partition(|(shelter, _)| alarm == 0 || !safe(&self.grid[*shelter as usize]));
+        self.sheltered = staying;
+
+        for (shelter, mut entity) in leaving {
+            if let Some((width, height, j)) = building_dimensions(&self.grid[shelter as usize]) {
+                if let Some(spot) = pick_random_location_building_outline(&self.grid, width, height, j, shelter, &mut self.rng) {
+                    let (x, y) = vec_from_grid(spot);
+                    entity.position_x = x as u16 * CELL_SIZE as u16;
+                    entity.position_y = y as u16 * CELL_SIZE as u16;
+                }
+            }
+
+            self.entities.push(entity);
+        }
+
+        if self.alarm == 0 {
+            return;
+        }
+
+        let shelters: Vec<u16> = self.grid.iter().enumerate()
+            .filter(|(_, cell)| matches!(cell, CellState::House(BuildingState::Solid, 0, _) | CellState::House2(BuildingState::Solid, 0, _) | CellState::Church(BuildingState::Solid, 0, _)))
+            .map(|(index, _)| index as u16)
+            .collect();
+
+        for index in (0..self.entities.len()).rev() {
+            let entity = &self.entities[index];
+            if !entity.alive() || !matches!(entity.entity_type, EntityType::VillagerClan(VillagerClan::Villager | VillagerClan::Farmer(_))) {
+                continue;
+            }
+
+            let cell = center_cell(entity.position_x, entity.position_y);
+            if let Some(shelter) = self.adjacent_building(cell, safe) {
+                let mut entity = self.entities.remove(index);
+                entity.velocity = (0, 0);
+                entity.goal = None;
+                entity.target = None;
+                entity.order = None;
+                entity.selected = false;
+                self.sheltered.push((shelter, entity));
+                continue;
+            }
+
+            // right when the bell rings, then once a second for the ones that got lost on the way
+            if entity.order.is_none() && (self.alarm == ALARM_DURATION - 1 || self.tick == 0) {
+                self.entities[index].order = self.closest_reachable(cell, &shelters).map(Order::Move);
+            }
+        }
+    }
+
+    // Keeps an entity busy with the closest building it can see that needs some work, by walking to a
+    // random free cell of its outline. The spot is dropped once there's nothing left to do next to it
+    // Returns whether the entity has a spot to work from
+    fn find_work_spot(&mut self, index: usize, sight: u16, needs_work: impl Fn(&CellState) -> bool) -> bool {
+        let entity = &self.entities[index];
+        if let Some(Goal::Cell(spot)) = entity.goal {
+            if self.adjacent_building(spot, &needs_work).is_some() {
+                return true;
+            }
+
+            self.entities[index].goal = None;
+        }
+
+        // look around once a second, like the illagers
+        if self.tick != 0 {
+            return false;
+        }
+
+        let entity = &self.entities[index];
+        let closest = (0..AREA as u16)
+            .filter(|cell| needs_work(&self.grid[*cell as usize]))
+            .map(|cell| {
+                let (x, y) = vec_from_grid(cell);
+                (cell, entity.distance_to(x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16))
+            })
+            .filter(|(_, distance)| *distance <= sight)
+            .min_by_key(|(_, distance)| *distance);
+
+        let Some((building, _)) = closest else {
+            return false;
+        };
+
+        let Some((width, height, j)) = building_dimensions(&self.grid[building as usize]) else {
+            return false;
+        };
+
+        let spot = pick_random_location_building_outline(&self.grid, width, height, j, building, &mut self.rng);
+        self.entities[index].goal = spot.map(Goal::Cell);
+        spot.is_some()
+    }
+
+    // Sub-cell of a building right next to a cell, if there's one that matches
+    fn adjacent_building(&self, cell: u16, matching: impl Fn(&CellState) -> bool) -> Option<u16> {
+        get_neighbours(cell).into_iter().find(|neighbour| *neighbour != u16::MAX && matching(&self.grid[*neighbour as usize]))
+    }
+
+    // Sets every sub-cell of a house or church to a new state and integrity
+    fn set_building(&mut self, cell: u16, state: BuildingState, integrity: u8) {
+        let Some((width, height, j)) = building_dimensions(&self.grid[cell as usize]) else {
+            return;
+        };
+
+        for cell in building_cells(width, height, j, cell) {
+            if let CellState::House(old, _, old_integrity) | CellState::House2(old, _, old_integrity) | CellState::Church(old, _, old_integrity) | CellState::Bell(old, old_integrity) = &mut self.grid[cell as usize] {
+                *old = state;
+                *old_integrity = integrity;
+            }
+        }
+    }
+
+    // Puts out a burning building, whatever burnt down stays that way
+    fn extinguish(&mut self, cell: u16) {
+        if let Some((BuildingState::Burning, integrity)) = building_integrity(&self.grid[cell as usize]) {
+            self.set_building(cell, BuildingState::Solid, integrity);
+            self.sounds.push(Noise::Ting);
+        }
+    }
+
+    // One more unit of work on a ruin, the first one is what costs emeralds
+    fn rebuild(&mut self, cell: u16) {
+        let Some((BuildingState::Destroyed, integrity)) = building_integrity(&self.grid[cell as usize]) else {
+            return;
+        };
+
+        if integrity == 0 {
+            let Some(emeralds) = self.emeralds[0].checked_sub(REBUILD_COST) else {
+                return;
+            };
+            self.emeralds[0] = emeralds;
+        }
+
+        let full = match self.grid[cell as usize] {
+            CellState::Church(..) => CHURCH_INTEGRITY,
+            CellState::Bell(..) => BELL_INTEGRITY,
+            _ => HOUSE_INTEGRITY,
+        };
+        if integrity + 1 >= full {
+            self.set_building(cell, BuildingState::Solid, full);
+            self.sounds.push(Noise::Ting);
+        } else {
+            self.set_building(cell, BuildingState::Destroyed, integrity + 1);
+            self.sounds.push(Noise::Anvil);
+        }
+    }
+
+    // One more unit of repairs on the broken golem at the given position
+    fn repair_golem(&mut self, x: u16, y: u16) {
+        let Some(golem) = self.entities.iter_mut().find(|golem| (golem.position_x, golem.position_y) == (x, y) && !golem.alive()) else {
+            return;
+        };
+
+        let EntityType::VillagerClan(VillagerClan::Golem(repairs, state)) = &mut golem.entity_type else {
+            return;
+        };
+
+        *repairs += 1;
+        if *repairs >= GOLEM_REPAIR {
+            *repairs = 0;
+            *state = GolemState::Idle;
+            golem.health = golem.entity_type.stats().health;
+            self.sounds.push(Noise::Ting);
+        } else {
+            self.sounds.push(Noise::Anvil);
+        }
+    }
+
+    // Closest standing house, church, barricade or growing farm sub-cell a pillager can shoot at without moving, as a
+    // pixel position
+    fn flammable_in_reach(&self, pillager: &Entity) -> Option<(u16, u16)> {
+        let reach = pillager.entity_type.stats().reach as u16;
+        let (center_x, center_y) = vec_from_grid(center_cell(pillager.position_x, pillager.position_y));
+        let cells = (reach / CELL_SIZE as u16) as u8;
+
+        let xs = center_x.saturating_sub(cells)..(center_x + cells + 1).min(GRID_SIZE_X);
+        xs.flat_map(|x| (center_y.saturating_sub(cells)..(center_y + cells + 1).min(GRID_SIZE_Y)).map(move |y| (x, y)))
+            .filter(|(x, y)| matches!(self.grid[grid_from_vec(*x, *y) as usize], CellState::House(BuildingState::Solid, ..) | CellState::House2(BuildingState::Solid, ..) | CellState::Church(BuildingState::Solid, ..) | CellState::Barricade(0) | CellState::Farm(_, 1.., 0)))
+            .map(|(x, y)| (x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16))
+            .filter(|(x, y)| pillager.distance_to(*x, *y) <= reach)
+            .min_by_key(|(x, y)| pillager.distance_to(*x, *y))
+    }
+
+    // Evokers casting their spells, and vex running out of time
+    fn update_spells(&mut self) {
+        for index in 0..self.entities.len() {
+            let entity = &mut self.entities[index];
+            if !entity.alive() {
+                continue;
+            }
+
+            let cooldown = match &mut entity.entity_type {
+                EntityType::IllagerClan(IllagerClan::Vex(life), _) => {
+                    *life = life.saturating_sub(1);
+                    if *life == 0 {
+                        entity.health = 0;
+                    }
+                    continue;
+                }
+
+                EntityType::IllagerClan(IllagerClan::Evoker(cooldown), _) => {
+                    *cooldown = cooldown.saturating_sub(1);
+                    *cooldown
+                }
+
+                _ => continue,
+            };
+
+            let evoker = &self.entities[index];
+            let target = self.entities.iter()
+                .filter(|other| other.alive() && !other.is_illager())
+                .map(|other| (other.position_x, other.position_y, evoker.distance_to(other.position_x, other.position_y)))
+                .filter(|(_, _, distance)| *distance <= EVOKER_SIGHT)
+                .min_by_key(|(_, _, distance)| *distance);
+
+            let Some((target_x, target_y, distance)) = target.filter(|_| cooldown == 0) else {
+                continue;
+            };
+
+            if distance <= FANG_RANGE {
+                self.cast_fangs(index, target_x, target_y);
+            } else {
+                self.summon_vex(index);
+            }
+
+            // the attack cooldown is only there to show the casting pose for a bit
+            let evoker = &mut self.entities[index];
+            if let EntityType::IllagerClan(IllagerClan::Evoker(cooldown), _) = &mut evoker.entity_type {
+                *cooldown = EVOKER_SPELL_COOLDOWN;
+            }
+            evoker.cooldown = ATTACK_ANIMATION_FRAMES;
+            evoker.face_toward(target_x, target_y);
+        }
+    }
+
+    // Fangs come out of the ground one after the other toward the target, until something's in the way
+    fn cast_fangs(&mut self, evoker: usize, target_x: u16, target_y: u16) {
+        let (start_x, start_y) = (self.entities[evoker].position_x as i32, self.entities[evoker].position_y as i32);
+        let (dx, dy) = (target_x as i32 - start_x, target_y as i32 - start_y);
+        let length = (dx * dx + dy * dy).isqrt().max(1);
+        let half = CELL_SIZE as i32 / 2;
+
+        let mut bitten: Vec<usize> = Vec::new();
+        for i in 1..=FANG_COUNT {
+            let x = start_x + half + dx * i * FANG_SPACING / length;
+            let y = start_y + half + dy * i * FANG_SPACING / length;
+            if !pixel_loc_in_grid_bounds(x as i16, y as i16) {
+                break;
+            }
+
+            let cell = grid_from_vec((x / CELL_SIZE as i32) as u8, (y / CELL_SIZE as i32) as u8);
+            if !matches!(self.grid[cell as usize], CellState::Empty) {
+                break;
+            }
+
+            for _ in 0..3 {
+                Self::summon_particle(&mut self.particles, Particle {
+                    x: (x + self.rng.i32(-2..=2)).clamp(0, CELL_SIZE as i32 * GRID_SIZE_X as i32 - 1) as u16,
+                    y: y as u16,
+                    life: 3 + self.rng.u8(0..3),
+                    horizontal_velocity: 0,
+                });
+            }
+
+            // everyone standing on a fang gets bitten, but only once per spell
+            for (index, entity) in self.entities.iter().enumerate() {
+                let (left, top) = (entity.position_x as i32, entity.position_y as i32);
+                let on_fang = (left..left + CELL_SIZE as i32).contains(&x) && (top..top + CELL_SIZE as i32).contains(&y);
+                if on_fang && entity.alive() && !entity.is_illager() && !bitten.contains(&index) {
+                    bitten.push(index);
+                }
+            }
+        }
+
+        self.sounds.push(Noise::Fangs);
+        for index in bitten {
+            self.hurt(index, FANG_DAMAGE);
+        }
+    }
+
+    // Vex pop out right next to the evoker. They're part of the spell, so they don't count as units spawned
+    fn summon_vex(&mut self, evoker: usize) {
+        let (x, y) = (self.entities[evoker].position_x, self.entities[evoker].position_y);
+        for _ in 0..VEX_PER_SPELL {
+            let x = x.saturating_add_signed(self.rng.i16(-4..=4));
+            let y = y.saturating_add_signed(self.rng.i16(-4..=4));
+            self.entities.push(Entity::new(EntityType::IllagerClan(IllagerClan::Vex(VEX_LIFETIME), IllagerState::Idle), x, y));
+        }
+
+        self.sounds.push(Noise::Summon);
+    }
+
+    // Steering, collision and separation of the entities
+    fn update_entities(&mut self) {
+        // landmarks illagers march on when they have nothing better to do: the bell (right in the
+        // middle of the village) or the churches still standing if it can't be reached
+        let center = grid_from_vec(GRID_SIZE_X / 2, GRID_SIZE_Y / 2);
+        let mut landmarks: Vec<u16> = self.grid.iter().enumerate()
+            .filter(|(_, cell)| matches!(cell, CellState::Bell(BuildingState::Solid, _) | CellState::Church(BuildingState::Solid | BuildingState::Burning, 0, _)))
+            .map(|(index, _)| index as u16)
+            .collect();
+        landmarks.sort_by_key(|landmark| (!matches!(self.grid[*landmark as usize], CellState::Bell(..)), pathfinding::distance(center, *landmark)));
+
+        // push entities that are too close to each other apart, so they don't stack on the same pixel
+        // (summed in i16 and clamped, a big crowd on one spot would overflow an i8)
+        let pushes: Vec<(i8, i8)> = self.entities.iter().enumerate().map(|(i, a)| {
+            let (push_x, push_y) = self.entities.iter().enumerate().filter(|(j, _)| i != *j).fold((0i16, 0i16), |(push_x, push_y), (j, b)| {
+                let dx = a.position_x as i16 - b.position_x as i16;
+                let dy = a.position_y as i16 - b.position_y as i16;
+                if dx.abs() >= SEPARATION_DISTANCE || dy.abs() >= SEPARATION_DISTANCE {
+                    return (push_x, push_y);
+                }
+
+                // entities on the exact same pixel get split by their order
+                let dx = if dx == 0 && dy == 0 { if i < j { -1 } else { 1 } } else { dx.signum() };
+                (push_x + dx * SEPARATION_STRENGTH as i16, push_y + dy.signum() * SEPARATION_STRENGTH as i16)
+            });
+            let clamp = |push: i16| push.clamp(i8::MIN as i16, i8::MAX as i16) as i8;
+            (clamp(push_x), clamp(push_y))
+        }).collect();
+
+        for (entity, push) in self.entities.iter_mut().zip(pushes) {
+            // broken golems stay where they fell
+            if !entity.alive() {
+                continue;
+            }
+
+            let engaged = entity.engaged();
+            let flying = matches!(entity.entity_type, EntityType::IllagerClan(IllagerClan::Vex(_), _));
+            let Entity { position_x, position_y, entity_type, goal, target, order, velocity, remainder, facing, .. } = entity;
+            let speed = entity_type.speed();
+            let cell = center_cell(*position_x, *position_y);
+            let toward = |x: u16, y: u16| {
+                let direction_x = (x as i16 - *position_x as i16).signum() as i8;
+                let direction_y = (y as i16 - *position_y as i16).signum() as i8;
+                steer(direction_x, direction_y, speed)
+            };
+
+            // look for the first landmark we can reach (once a second, as it might compute a few flow fields)
+            // unless there's a rally flag to gather at
+            let illager = matches!(entity_type, EntityType::IllagerClan(..));
+            let rally = self.rally[illager as usize].map(Goal::Cell);
+            if goal.is_none() && illager && rally.is_none() && self.tick == 0 {
+                *goal = landmarks
+                    .iter()
+                    .map(|landmark| Goal::Building(*landmark))
+                    .find(|goal| self.flow_fields.next_step(&self.grid, cell, *goal).is_some());
+            }
+
+            // move orders beat everything, attack orders only wait for the fights on the way
+            let ordered = match *order {
+                Some(Order::Move(objective)) => Some(objective),
+                Some(Order::Attack(objective)) if target.is_none() => Some(objective),
+                _ => None,
+            };
+
+            if let Some(objective) = ordered {
+                match self.flow_fields.next_step(&self.grid, cell, objective) {
+                    // made it, illagers sent to a building stay there to wreck it
+                    Some(next) if next == cell => {
+                        if let Some(Order::Attack(building @ Goal::Building(_))) = *order {
+                            *goal = Some(building);
+                        }
+                        *order = None;
+                        *velocity = (0, 0);
+                    }
+
+                    Some(next) => {
+                        let (next_x, next_y) = vec_from_grid(next);
+                        *velocity = toward(next_x as u16 * CELL_SIZE as u16, next_y as u16 * CELL_SIZE as u16);
+                    }
+
+                    None => {
+                        *order = None;
+                        *velocity = (0, 0);
+                    }
+                }
+            } else if let Some((target_x, target_y)) = *target {
+                // pillagers keep their distance
+                let too_close = matches!(entity_type, EntityType::IllagerClan(IllagerClan::Pillager, _))
+                    && target_x.abs_diff(*position_x).max(target_y.abs_diff(*position_y)) < PILLAGER_MIN_DISTANCE;
+
+                // chasing someone, we stand still once they're in reach and let the fight happen
+                let target_cell = center_cell(target_x, target_y);
+                *velocity = if too_close {
+                    let (away_x, away_y) = toward(target_x, target_y);
+                    (-away_x, -away_y)
+                } else if engaged {
+                    (0, 0)
+                } else if flying || pathfinding::distance(cell, target_cell) <= pathfinding::DIAGONAL_COST {
+                    toward(target_x, target_y)
+                } else {
+                    // targets are only ours (and usually on the move), not worth a flow field
+                    let goal = if matches!(self.grid[target_cell as usize], CellState::Empty) { Goal::Cell(target_cell) } else { Goal::Building(target_cell) };
+                    match pathfinding::next_step(&self.grid, cell, goal) {
+                        // right next to the building, but not close enough yet
+                        Some(next) if next == cell => toward(target_x, target_y),
+                        Some(next) => {
+                            let (next_x, next_y) = vec_from_grid(next);
+                            toward(next_x as u16 * CELL_SIZE as u16, next_y as u16 * CELL_SIZE as u16)
+                        }
+                        None => (0, 0),
+                    }
+                };
+            } else if let Some(objective) = goal.or(rally) {
+                // units with an objective (or the rally flag) head toward the next cell of the flow field
+                match self.flow_fields.next_step(&self.grid, cell, objective) {
+                    // made it, make some room for the others
+                    Some(next) if next == cell => *velocity = (0, 0),
+
+                    Some(next) => {
+                        let (next_x, next_y) = vec_from_grid(next);
+                        *velocity = toward(next_x as u16 * CELL_SIZE as u16, next_y as u16 * CELL_SIZE as u16);
+                    }
+
+                    // the way got blocked, we'll look for something else
+                    None => {
+                        *goal = None;
+                        *velocity = (0, 0);
+                    }
+                }
+            } else if self.rng.u8(0..WANDER_CHANCE_INV) == 0 {
+                // everyone else strolls around, stopping every now and then
+                *velocity = if self.rng.bool() {
+                    let (direction_x, direction_y) = direction_offset(random_direction_with_diagonal(&mut self.rng));
+                    steer(direction_x, direction_y, speed / 2)
+                } else {
+                    (0, 0)
+                };
+            }
+
+            if *velocity != (0, 0) {
+                *facing = direction_from_offset(velocity.0.signum(), velocity.1.signum());
+            }
+
+            // move one axis at a time, so we slide along walls instead of getting stuck on them
+            // entities that are already stuck in something (a building went up) are free to walk out
+            // vex fly over everything, they only have to stay on the map
+            let stuck = flying || !entity_fits(&self.grid, *position_x as i16, *position_y as i16);
+            let (mut x, mut y) = (*position_x as i16, *position_y as i16);
+
+            let step_x = integrate(velocity.0.saturating_add(push.0), &mut remainder.0);
+            if stuck || entity_fits(&self.grid, x + step_x, y) {
+                x += step_x;
+            } else {
+                (velocity.0, remainder.0) = (0, 0);
+            }
+
+            let step_y = integrate(velocity.1.saturating_add(push.1), &mut remainder.1);
+            if stuck || entity_fits(&self.grid, x, y + step_y) {
+                y += step_y;
+            } else {
+                (velocity.1, remainder.1) = (0, 0);
+            }
+
+            let max_x = (CELL_SIZE as i16) * (GRID_SIZE_X as i16 - 1);
+            let max_y = (CELL_SIZE as i16) * (GRID_SIZE_Y as i16 - 1);
+            (*position_x, *position_y) = (x.clamp(0, max_x) as u16, y.clamp(0, max_y) as u16);
+        }
+    }
+
+    // Attacks, hit animations and deaths
+    fn update_combat(&mut self) {
+        for index in 0..self.entities.len() {
+            let attacker = &mut self.entities[index];
+            attacker.cooldown = attacker.cooldown.saturating_sub(1);
+            let stats = attacker.entity_type.stats();
+            if !attacker.alive() || stats.damage == 0 || attacker.cooldown > 0 {
+                continue;
+            }
+
+            // pillagers shoot at whatever they're aiming at
+            if matches!(attacker.entity_type, EntityType::IllagerClan(IllagerClan::Pillager, _)) {
+                if let Some((x, y)) = attacker.target.filter(|_| attacker.engaged()) {
+                    self.fire_bolt(index, x, y);
+                }
+                continue;
+            }
+
+            // hit the closest enemy in reach, if there's any
+            let attacker = &self.entities[index];
+            let target = self.entities.iter().enumerate()
+                .filter(|(_, other)| other.alive() && other.is_illager() != attacker.is_illager())
+                .map(|(other_index, other)| {
+                    let dx = other.position_x.abs_diff(attacker.position_x);
+                    let dy = other.position_y.abs_diff(attacker.position_y);
+                    (other_index, dx.max(dy))
+                })
+                .filter(|(_, distance)| *distance <= stats.reach as u16)
+                .min_by_key(|(_, distance)| *distance)
+                .map(|(other_index, _)| other_index);
+
+            if let Some(target) = target {
+                self.strike(index, target);
+                continue;
+            }
+
+            // otherwise illagers wreck the building they marched on once they're next to it
+            let attacker = &self.entities[index];
+            let Some(Goal::Building(building)) = attacker.goal.filter(|_| attacker.is_illager()) else {
+                continue;
+            };
+
+            let cell = center_cell(attacker.position_x, attacker.position_y);
+            match building_integrity(&self.grid[building as usize]) {
+                Some((BuildingState::Destroyed, _)) => self.entities[index].goal = None,
+                Some(_) if self.flow_fields.next_step(&self.grid, cell, Goal::Building(building)) == Some(cell) => {
+                    self.strike_building(index, building)
+                }
+                _ => {}
+            }
+        }
+
+        // the attack sprites stay up for a bit after each hit
+        for entity in self.entities.iter_mut() {
+            let attacking = entity.engaged()
+                || (entity.cooldown > 0 && entity.cooldown + ATTACK_ANIMATION_FRAMES > entity.entity_type.stats().cooldown);
+            match &mut entity.entity_type {
+                EntityType::IllagerClan(_, state) => {
+                    *state = if attacking { IllagerState::Action } else { IllagerState::Idle };
+                }
+
+                EntityType::VillagerClan(VillagerClan::Golem(_, state)) if entity.health > 0 => {
+                    *state = if attacking { GolemState::Attack } else { GolemState::Idle };
+                }
+
+                _ => {}
+            }
+        }
+
+        self.resolve_deaths();
+    }
+
+    // Makes the attacker hit the target, and starts its cooldown
+    fn strike(&mut self, attacker: usize, target: usize) {
+        let (target_x, target_y) = (self.entities[target].position_x, self.entities[target].position_y);
+        let entity = &mut self.entities[attacker];
+        entity.cooldown = entity.entity_type.stats().cooldown;
+        entity.face_toward(target_x, target_y);
+
+        let damage = entity.entity_type.stats().damage;
+        self.hurt(target, damage);
+    }
+
+    // Makes the attacker hit a building, and starts its cooldown
+    fn strike_building(&mut self, attacker: usize, building: u16) {
+        let (x, y) = vec_from_grid(building);
+        let entity = &mut self.entities[attacker];
+        entity.cooldown = entity.entity_type.stats().cooldown;
+        entity.face_toward(x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16);
+
+        let damage = entity.entity_type.stats().damage;
+        self.damage_building(building, damage);
+    }
+
+    // Shoots a bolt from the center of the shooter toward the center of the target
+    fn fire_bolt(&mut self, shooter: usize, target_x: u16, target_y: u16) {
+        let entity = &mut self.entities[shooter];
+        let stats = entity.entity_type.stats();
+        entity.cooldown = stats.cooldown;
+        entity.face_toward(target_x, target_y);
+
+        let dx = target_x as i32 - entity.position_x as i32;
+        let dy = target_y as i32 - entity.position_y as i32;
+        let length = (dx * dx + dy * dy).isqrt().max(1);
+        let half = CELL_SIZE as u16 / 2;
+
+        self.projectiles.push(Projectile {
+            x: (entity.position_x + half) * 16,
+            y: (entity.position_y + half) * 16,
+            velocity: ((dx * BOLT_SPEED / length) as i8, (dy * BOLT_SPEED / length) as i8),
+            life: BOLT_LIFETIME,
+            damage: stats.damage,
+            flaming: !matches!(self.grid[center_cell(target_x, target_y) as usize], CellState::Empty),
+        });
+        self.sounds.push(Noise::Shoot);
+    }
+
+    // Moves the bolts, which stop at the first thing in their way
+    // They only hurt villagers and golems, and fly right past other illagers
+    fn update_projectiles(&mut self) {
+        let mut projectiles = std::mem::take(&mut self.projectiles);
+        projectiles.retain_mut(|bolt| {
+            bolt.x = bolt.x.wrapping_add_signed(bolt.velocity.0 as i16);
+            bolt.y = bolt.y.wrapping_add_signed(bolt.velocity.1 as i16);
+            bolt.life = bolt.life.saturating_sub(1);
+
+            let (x, y) = ((bolt.x / 16) as i16, (bolt.y / 16) as i16);
+            if bolt.life == 0 || !pixel_loc_in_grid_bounds(x, y) {
+                return false;
+            }
+
+            let cell = grid_from_vec((x / CELL_SIZE as i16) as u8, (y / CELL_SIZE as i16) as u8);
+            if !matches!(self.grid[cell as usize], CellState::Empty) {
+                if bolt.flaming {
+                    self.ignite(cell);
+                }
+                return false;
+            }
+
+            let hit = self.entities.iter().position(|entity| {
+                let (left, top) = (entity.position_x as i16, entity.position_y as i16);
+                entity.alive()
+                    && !entity.is_illager()
+                    && (left + HITBOX_MIN..=left + HITBOX_MAX).contains(&x)
+                    && (top + HITBOX_MIN..=top + HITBOX_MAX).contains(&y)
+            });
+
+            if let Some(hit) = hit {
+                self.hurt(hit, bolt.damage);
+                return false;
+            }
+
+            true
+        });
+        self.projectiles = projectiles;
+    }
+
+    // Every BURN_INTERVAL frames, fire spreads to the neighbours of the burning cells and eats away
+    // at what's burning. Houses and churches burn down to ruins, everything else to the ground
+    fn update_fires(&mut self) {
+        if !self.tick.is_multiple_of(BURN_INTERVAL) {
+            return;
+        }
+
+        let burning: Vec<u16> = self.grid.iter().enumerate()
+            .filter(|(_, cell)| on_fire(cell))
+            .map(|(index, _)| index as u16)
+            .collect();
+
+        // rocks (and anything else that can't burn) act as firebreaks
+        for cell in burning.iter() {
+            for neighbour in get_neighbours(*cell) {
+                if neighbour != u16::MAX && flammable(&self.grid[neighbour as usize]) && self.rng.u8(..FIRE_SPREAD_CHANCE_INV) == 0 {
+                    self.ignite(neighbour);
+                }
+            }
+        }
+
+        // only the top left sub-cell, the fuel / integrity is shared by the whole thing
+        for cell in burning {
+            match self.grid[cell as usize] {
+                CellState::House(BuildingState::Burning, 0, _) | CellState::House2(BuildingState::Burning, 0, _) | CellState::Church(BuildingState::Burning, 0, _) => {
+                    self.damage_building(cell, 1);
+                }
+
+                CellState::Tree(0, fuel @ 1..) | CellState::Hay(0, fuel @ 1..) | CellState::Stand(0, fuel @ 1..) | CellState::Farm(0, _, fuel @ 1..) | CellState::Barricade(fuel @ 1..) => {
+                    self.set_fuel(cell, fuel - 1);
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    // Sets the fuel left of every sub-cell of a tree, hay, stand, farm or barricade, which is gone once it reaches 0
+    // (burning farms lose their crops)
+    fn set_fuel(&mut self, cell: u16, fuel: u8) {
+        let Some((width, height, j)) = building_dimensions(&self.grid[cell as usize]) else {
+            return;
+        };
+
+        for cell in building_cells(width, height, j, cell) {
+            let state = &mut self.grid[cell as usize];
+            match state {
+                CellState::Tree(_, left) | CellState::Hay(_, left) | CellState::Stand(_, left) | CellState::Barricade(left) if fuel > 0 => *left = fuel,
+                CellState::Farm(_, growth, left) if fuel > 0 => (*growth, *left) = (0, fuel),
+                CellState::Tree(..) | CellState::Hay(..) | CellState::Stand(..) | CellState::Farm(..) | CellState::Barricade(_) => *state = CellState::Empty,
+                _ => {}
+            }
+        }
+    }
+
+    // Takes away some integrity from every sub-cell of a house, church or bell. Standing buildings catch
+    // fire (bells don't), and they're destroyed (and looted) once there's nothing left of them
+    pub(crate) fn damage_building(&mut self, cell: u16, damage: u8) {
+        let building = self.grid[cell as usize];
+        let (Some((width, height, j)), Some((state, integrity))) = (building_dimensions(&building), building_integrity(&building)) else {
+            return;
+        };
+
+        if matches!(state, BuildingState::Destroyed) {
+            return;
+        }
+
+        let integrity = integrity.saturating_sub(damage);
+        let bell = matches!(building, CellState::Bell(..));
+        let state = match integrity {
+            0 => BuildingState::Destroyed,
+            _ if bell => BuildingState::Solid,
+            _ => BuildingState::Burning,
+        };
+        self.set_building(cell, state, integrity);
+
+        if integrity > 0 {
+            self.sounds.push(match building {
+                CellState::Church(..) => Noise::TungTungTungSahour,
+                CellState::Bell(..) => Noise::Anvil,
+                _ => Noise::Burn,
+            });
+            return;
+        }
+
+        // KABOOM TYPE TIMING!!!!!!!!!!
+        self.sounds.push(Noise::KaboomNoise);
+        if !bell {
+            self.stats.buildings_burned += 1;
+        }
+        self.earn(1, match building {
+            CellState::Church(..) => self.income.church_loot,
+            CellState::Bell(..) => 0,
+            _ => self.income.house_loot,
+        });
+
+        for cell in building_cells(width, height, j, cell) {
+            let (dst_x, dst_y) = vec_from_grid(cell);
+            let (dst_x, dst_y) = (dst_x as u16 * CELL_SIZE as u16 + 5, dst_y as u16 * CELL_SIZE as u16 + 5);
+
+            for _ in 0..20 {
+                // add randomness to start x position
+                let rng_offset_x = self.rng.i16(-2..=2);
+                let rng_offset_y = self.rng.i16(-2..=2);
+                let rng_offset_life = self.rng.i8(-2..=7);
+
+                // max particle count... otherwise... we doodoo....
+                Self::summon_particle(&mut self.particles, Particle {
+                    x: dst_x.saturating_add_signed(rng_offset_x),
+                    y: dst_y.saturating_add_signed(rng_offset_y),
+                    life: 5u8.saturating_add_signed(rng_offset_life),
+                    horizontal_velocity: self.rng.i8(..),
+                });
+            }
+        }
+    }
+
+    // Sets a house, church, tree, hay, stand, farm or barricade on fire (chipping at buildings a bit)
+    fn ignite(&mut self, cell: u16) {
+        match self.grid[cell as usize] {
+            CellState::House(..) | CellState::House2(..) | CellState::Church(..) => self.damage_building(cell, 1),
+
+            CellState::Tree(_, 0) => self.set_fuel(cell, TREE_FUEL),
+            CellState::Hay(_, 0) => self.set_fuel(cell, HAY_FUEL),
+            CellState::Stand(_, 0) => self.set_fuel(cell, STAND_FUEL),
+            CellState::Barricade(0) => self.set_fuel(cell, BARRICADE_FUEL),
+            CellState::Farm(_, _, 0) => {
+                self.set_fuel(cell, FARM_FUEL);
+                self.sounds.push(Noise::Burn);
+            }
+
+            _ => {}
+        }
+    }
+
+    // Takes away some health from an entity. Deaths are handled at the end of the frame
+    pub(crate) fn hurt(&mut self, target: usize, damage: u8) {
+        let entity = &mut self.entities[target];
+        entity.health = entity.health.saturating_sub(damage);
+        self.sounds.push(Noise::Hit);
+    }
+
+    // Golems break down when they run out of health, everyone else is gone for good
+    fn resolve_deaths(&mut self) {
+        let mut bounty = 0u8;
+        for entity in self.entities.iter_mut().filter(|entity| !entity.alive()) {
+            match &mut entity.entity_type {
+                EntityType::VillagerClan(VillagerClan::Golem(repairs, state @ (GolemState::Idle | GolemState::Attack))) => {
+                    *state = GolemState::Broken;
+                    *repairs = 0;
+                    entity.velocity = (0, 0);
+                    entity.goal = None;
+                    entity.target = None;
+                    self.sounds.push(Noise::KaboomNoise);
+                    bounty = bounty.saturating_add(self.income.golem_bounty);
+                    self.stats.lost[0] += 1;
+                }
+
+                EntityType::VillagerClan(VillagerClan::Golem(..)) => {}
+
+                EntityType::VillagerClan(_) => {
+                    self.sounds.push(Noise::Death);
+                    bounty = bounty.saturating_add(self.income.kill_bounty);
+                    Self::death_particles(&mut self.particles, &mut self.rng, entity);
+                    self.stats.lost[0] += 1;
+                }
+
+                // vex that ran out of time just vanish, and the ones that got hit don't count as units lost
+                EntityType::IllagerClan(IllagerClan::Vex(0), _) => {}
+
+                EntityType::IllagerClan(IllagerClan::Vex(_), _) => {
+                    self.sounds.push(Noise::Death);
+                    Self::death_particles(&mut self.particles, &mut self.rng, entity);
+                }
+
+                EntityType::IllagerClan(..) => {
+                    self.sounds.push(Noise::Death);
+                    Self::death_particles(&mut self.particles, &mut self.rng, entity);
+                    self.stats.lost[1] += 1;
+                }
+            }
+        }
+
+        self.earn(1, bounty);
+        self.entities.retain(|entity| entity.alive() || matches!(entity.entity_type, EntityType::VillagerClan(VillagerClan::Golem(..))));
+    }
+
+    fn death_particles(particles: &mut Vec<Particle>, rng: &mut fastrand::Rng, entity: &Entity) {
+        let (x, y) = (entity.position_x + CELL_SIZE as u16 / 2, entity.position_y + CELL_SIZE as u16 / 2);
+        for _ in 0..DEATH_PARTICLE_COUNT {
+            Self::summon_particle(particles, Particle {
+                x: x.saturating_add_signed(rng.i16(-2..=2)),
+                y: y.saturating_add_signed(rng.i16(-2..=2)),
+                life: 4 + rng.u8(0..4),
+                horizontal_velocity: rng.i8(..),
+            });
+        }
+    }
+
+    // Gamepad of the illager AI: once in a while it puts its cursor on a free cell of the border and
+    // summons someone, or calls in reinforcements when it can (which it keeps a few emeralds for)
+    fn ai_inputs(&mut self) -> u8 {
+        if !self.frame.is_multiple_of(AI_THINK_INTERVAL) {
+            return 0;
+        }
+
+        let margin = self.margin;
+        let border: Vec<u16> = (0..AREA as u16)
+            .filter(|cell| at_border(*cell, margin) && matches!(self.grid[*cell as usize], CellState::Empty))
+            .collect();
+        if border.is_empty() {
+            return 0;
+        }
+
+        let class = if self.action_cooldowns[1][2] == 0 && self.emeralds[1] >= ACTION_PRICES[5] {
+            5
+        } else {
+            let class = self.rng.u8(0..3);
+            if self.emeralds[1] < PRICES[3 + class as usize].saturating_add(AI_SAVINGS) {
+                return 0;
+            }
+            class
+        };
+
+        self.cursors[1] = border[self.rng.u32(..border.len() as u32) as usize];
+        self.current_selected_class[1] = class;
+        BUTTON_1
+    }
+
+    // Passive income and raid waves of the illagers
+    fn update_income(&mut self) {
+        let IllagerIncome { passive_interval, passive, raid_wave_interval, raid_wave_bonus, .. } = self.income;
+        if passive_interval > 0 && self.frame > 0 && self.frame.is_multiple_of(passive_interval as u32) {
+            self.earn(1, passive);
+        }
+
+        if self.next_raid_wave() == Some(raid_wave_interval) && self.frame > 0 {
+            self.earn(1, raid_wave_bonus);
+            self.sounds.push(Noise::Raid);
+        }
+    }
+
+    // Frames left until the next raid wave, if they're turned on
+    pub(crate) fn next_raid_wave(&self) -> Option<u32> {
+        let interval = self.income.raid_wave_interval;
+        (interval > 0).then(|| interval - self.frame % interval)
+    }
+
+    fn earn(&mut self, player: usize, emeralds: u8) {
+        self.emeralds[player] = self.emeralds[player].saturating_add(emeralds);
+        self.stats.emeralds_earned[player] = self.stats.emeralds_earned[player].saturating_add(emeralds as u16);
+    }
+
+    // Do particle effect shit
+    fn update_particles(&mut self) {
+        for (index, state) in self.grid.iter().enumerate() {
+            match state {
+                // make sure we only look at the TOP LEFT sub-cell of the house
+                // everything will now use that local reference frame
+                CellState::House(BuildingState::Burning, 0, _) | CellState::House2(BuildingState::Burning, 0, _) => {
+                    let (dst_x, dst_y) = vec_from_grid(index as u16);
+                    let (mut dst_x, mut dst_y) = (dst_x as u16 * CELL_SIZE as u16, dst_y as u16 * CELL_SIZE as u16);
+
+                    // make sure the smoke starts at the door
+                    dst_x += 8;
+                    dst_y += 14;
+
+                    for _ in 0..3 {
+                        // add randomness to start x position
+                        let rng_offset_x = self.rng.u16(0..4);
+                        let rng_offset_life = self.rng.u8(0..4);
+                        Self::summon_particle(&mut self.particles, Particle {
+                            x: dst_x + rng_offset_x,
+                            y: dst_y,
+                            life: 16u8 + rng_offset_life,
+                            horizontal_velocity: 0,
+                        });
+                    }
+                }
+
+                // burning trees, hay, stands and barricades smoke from the middle
+                CellState::Tree(0, 1..) | CellState::Hay(0, 1..) | CellState::Stand(0, 1..) | CellState::Barricade(1..) => {
+                    let (dst_x, dst_y) = vec_from_grid(index as u16);
+                    let (dst_x, dst_y) = (dst_x as u16 * CELL_SIZE as u16 + 8, dst_y as u16 * CELL_SIZE as u16 + 5);
+
+                    for _ in 0..2 {
+                        let rng_offset_x = self.rng.u16(0..4);
+                        let rng_offset_life = self.rng.u8(0..4);
+                        Self::summon_particle(&mut self.particles, Particle {
+                            x: dst_x + rng_offset_x,
+                            y: dst_y,
+                            life: 12u8 + rng_offset_life,
+                            horizontal_velocity: 0,
+                        });
+                    }
+                }
+
+                _ => continue,
+            }
+        }
+
+        for Particle { x, y, life, horizontal_velocity } in self.particles.iter_mut() {
+            *y = y.wrapping_sub(1);
+
+            // randomize x spread
+            if self.rng.bool() {
+                *x = x.wrapping_add_signed(self.rng.i16(-1..=1));
+            }
+
+            *life = life.saturating_sub(1);
+
+            if horizontal_velocity.unsigned_abs() < 64 && self.rng.bool() {
+                *x = x.saturating_add_signed((*horizontal_velocity / 4) as i16);
+            } else {
+                *x = x.saturating_add_signed((*horizontal_velocity / 32) as i16);
+            }
+
+            *horizontal_velocity /= 2;
+        }
+
+        self.particles.retain(|Particle { x, y, life, .. }| *life > 0 && *x < (CELL_SIZE as u16 * GRID_SIZE_X as u16) && *y < (CELL_SIZE as u16 * GRID_SIZE_Y as u16));
+        self.particles.sort_unstable_by_key(|particle| particle.life);
+        self.particles.shrink_to_fit();
+    }
+
+    // Custom function to summon particle
+    fn summon_particle(particles: &mut Vec<Particle>, particle: Particle) {
+        if particles.len() > MAX_PARTICLE_COUNT {
+            // holy fuck... this is so bad.... lol lmao
+            particles.sort_unstable_by_key(|particle| particle.life);
+            particles.remove(particles.len() - 1);
+            particles.push(particle);
+            particles.sort_unstable_by_key(|particle| particle.life);
+        } else {
+            particles.push(particle);
+        }
+        /*
+        // max particle count... otherwise... we doodoo....
+        if particles.len() < MAX_PARTICLE_COUNT {
+        }
+        */
+    }
+    
+}
+
+// Burning houses and churches, and trees, hay, stands and barricades with fuel left
+fn on_fire(cell: &CellState) -> bool {
+    matches!(cell, CellState::House(BuildingState::Burning, ..) | CellState::House2(BuildingState::Burning, ..) | CellState::Church(BuildingState::Burning, ..)
+        | CellState::Tree(_, 1..) | CellState::Hay(_, 1..) | CellState::Stand(_, 1..) | CellState::Farm(_, _, 1..) | CellState::Barricade(1..))
+}
+
+// Cells fire can spread to
+fn flammable(cell: &CellState) -> bool {
+    matches!(cell, CellState::House(BuildingState::Solid, ..) | CellState::House2(BuildingState::Solid, ..) | CellState::Church(BuildingState::Solid, ..)
+        | CellState::Tree(_, 0) | CellState::Hay(_, 0) | CellState::Stand(_, 0) | CellState::Barricade(0) | CellState::Farm(_, 1.., 0))
+}
+
+// Whether a cell is on the outer ring of the playable part of the map
+fn at_border(index: u16, margin: u8) -> bool {
+    let (x, y) = vec_from_grid(index);
+    x == margin || x == GRID_SIZE_X - 1 - margin || y == margin || y == GRID_SIZE_Y - 1 - margin
+}
+
+// Cell under the center of an entity sprite
+fn center_cell(position_x: u16, position_y: u16) -> u16 {
+    let half = CELL_SIZE as u16 / 2;
+    grid_from_vec(((position_x + half) / CELL_SIZE as u16) as u8, ((position_y + half) / CELL_SIZE as u16) as u8)
+}
+
+// Adds the velocity (in 1/16th of a pixel) to the sub-pixel remainder, returns the whole pixels to move
+fn integrate(velocity: i8, remainder: &mut i8) -> i16 {
+    let total = *remainder as i16 + velocity as i16;
+    *remainder = (total % 16) as i8;
+    total / 16
+}
+
+// Velocity for walking in the given direction, diagonals are a bit slower so every direction
+// covers the same distance
+fn steer(direction_x: i8, direction_y: i8, speed: i8) -> (i8, i8) {
+    let speed = if direction_x != 0 && direction_y != 0 { speed * 11 / 16 } else { speed };
+    (direction_x * speed, direction_y * speed)
+}
+
+// FNV-1a, unlike the std hasher it is guaranteed to stay the same between platforms and releases
+pub(crate) struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // usize is 4 bytes on wasm32 and 8 on the host, hash it as a u64 on both
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+// The board and spawn fixtures are shared with the golden-frame tests
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::wasm4::{BUTTON_1, BUTTON_2, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_UP};
+
+    // A bit of everything: moving around, cycling classes and spawning on both sides
+    fn inputs(frame: u32) -> [u8; 2] {
+        const P1: [u8; 6] = [BUTTON_DOWN, 0, BUTTON_1, 0, BUTTON_2, BUTTON_RIGHT];
+        const P2: [u8; 6] = [BUTTON_1, BUTTON_UP, 0, BUTTON_LEFT, BUTTON_1, BUTTON_2];
+        let i = (frame / 7) as usize;
+        [P1[i % P1.len()], P2[(i * 5) % P2.len()]]
+    }
+
+    fn run(seed: u64, frames: u32) -> Simulation {
+        let mut sim = Simulation::new(seed);
+        for frame in 0..frames {
+            sim.step(inputs(frame));
+        }
+        sim
+    }
+
+    // Match on a fixed seed with nothing on the board
+    fn empty_sim() -> Simulation {
+        let mut sim = Simulation::new(1);
+        sim.grid.fill(CellState::Empty);
+        sim
+    }
+
+    // Places a multi-cell feature with its top left corner at (x, y)
+    pub(crate) fn place(sim: &mut Simulation, x: u8, y: u8, width: u8, height: u8, cell: impl Fn(u8) -> CellState) {
+        for j in 0..(width * height) {
+            sim.grid[grid_from_vec(x + j % width, y + j / width) as usize] = cell(j);
+        }
+    }
+
+    fn place_houses(sim: &mut Simulation, x: u8, y: u8, state: BuildingState) {
+        place(sim, x, y, 2, 2, |j| CellState::House(state, j, HOUSE_INTEGRITY));
+    }
+
+    // The bell sits in the middle of the map, like in generated villages
+    fn place_bell(sim: &mut Simulation) {
+        sim.grid[grid_from_vec(15, 15) as usize] = CellState::Bell(BuildingState::Solid, BELL_INTEGRITY);
+    }
+
+    // Someone free to wander around, at a pixel position
+    pub(crate) fn spawn(sim: &mut Simulation, entity_type: EntityType, x: u16, y: u16) {
+        sim.entities.push(Entity::new(entity_type, x, y));
+    }
+
+    // Someone holding their ground on the given cell
+    fn spawn_at(sim: &mut Simulation, entity_type: EntityType, x: u8, y: u8) {
+        let mut entity = Entity::new(entity_type, x as u16 * CELL_SIZE as u16, y as u16 * CELL_SIZE as u16);
+        entity.goal = Some(Goal::Cell(grid_from_vec(x, y)));
+        sim.entities.push(entity);
+    }
+
+    fn idle(sim: &mut Simulation, frames: u32) {
+        for _ in 0..frames {
+            sim.step([0, 0]);
+        }
+    }
+
+    // One press of the action button with the cursor on a cell
+    fn press_action(sim: &mut Simulation, player: usize, action: u8, x: u8, y: u8) {
+        sim.current_selected_class[player] = action;
+        sim.cursors[player] = grid_from_vec(x, y);
+        sim.step([0, 0]);
+        let mut inputs = [0, 0];
+        inputs[player] = BUTTON_1;
+        sim.step(inputs);
+    }
+
+    #[test]
+    fn same_seed_and_inputs_give_same_state() {
+        let a = run(42, 600);
+        let b = run(42, 600);
+        assert_eq!(a.checksum(), b.checksum());
+        assert_ne!(a.checksum(), run(43, 600).checksum());
+    }
+
+    #[test]
+    fn clone_continues_identically() {
+        let mut a = run(7, 300);
+        let mut b = a.clone();
+        for frame in 300..600 {
+            a.step(inputs(frame));
+            b.step(inputs(frame));
+        }
+        assert_eq!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn reset_gives_a_fresh_match() {
+        let setup = MatchSetup { mode: Mode::Ai, map_size: MapSize::Small, emeralds: [10, 250], seed: 3 };
+        let mut sim = run(42, 600);
+        sim.reset(&setup);
+        let mut fresh = Simulation::with_setup(&setup);
+        assert_eq!(sim.checksum(), fresh.checksum());
+
+        // and keeps on playing out the same
+        for frame in 0..600 {
+            sim.step(inputs(frame));
+            fresh.step(inputs(frame));
+        }
+        assert_eq!(sim.checksum(), fresh.checksum());
+    }
+
+    #[test]
+    fn outline_picks_are_the_same_on_every_platform() {
+        // 12 free cells around a house, the picks must not depend on the pointer width
+        let mut sim = empty_sim();
+        place_houses(&mut sim, 10, 10, BuildingState::Solid);
+        let mut rng = fastrand::Rng::with_seed(1);
+        let picks: Vec<u16> = (0..4)
+            .map(|_| pick_random_location_building_outline(&sim.grid, 2, 2, 0, grid_from_vec(10, 10), &mut rng).unwrap())
+            .collect();
+        assert_eq!(picks, [grid_from_vec(12, 11), grid_from_vec(9, 10), grid_from_vec(9, 11), grid_from_vec(12, 12)]);
+    }
+
+    #[test]
+    fn entities_walk_around_buildings() {
+        // a wall of rocks between the illagers and the bell, and houses in front of it
+        let mut sim = empty_sim();
+        place_bell(&mut sim);
+        for y in (0..20).step_by(2) {
+            place(&mut sim, 6, y, 2, 2, CellState::BigRock);
+        }
+        for x in [10, 14, 18] {
+            place_houses(&mut sim, x, 18, BuildingState::Solid);
+        }
+        for _ in 0..3 {
+            spawn(&mut sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 0, 0);
+        }
+
+        for _ in 0..1200 {
+            sim.step([0, 0]);
+            for entity in sim.entities.iter() {
+                assert!(entity_fits(&sim.grid, entity.position_x as i16, entity.position_y as i16));
+            }
+        }
+
+        // the illagers made it to the bell and didn't end up on top of each other
+        let illagers = &sim.entities;
+        assert_eq!(illagers.len(), 3);
+        for (i, a) in illagers.iter().enumerate() {
+            assert!(pathfinding::distance(center_cell(a.position_x, a.position_y), grid_from_vec(15, 15)) <= 2 * pathfinding::DIAGONAL_COST);
+            for b in illagers[i + 1..].iter() {
+                assert_ne!((a.position_x, a.position_y), (b.position_x, b.position_y));
+            }
+        }
+    }
+
+    #[test]
+    fn big_crowds_on_one_spot_spread_out() {
+        // enough villagers on the same pixel to overflow the push of a single frame
+        let mut sim = empty_sim();
+        for _ in 0..60 {
+            spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 150, 150);
+        }
+
+        idle(&mut sim, 10);
+        let first = &sim.entities[0];
+        let last = &sim.entities[59];
+        assert!(first.position_x < 150 && last.position_x > 150);
+    }
+
+    #[test]
+    fn fights_end_in_deaths_or_broken_golems() {
+        let mut sim = empty_sim();
+        spawn_at(&mut sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 10, 10);
+        spawn_at(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 11, 10);
+
+        // the villager can't fight back
+        let (mut attacked, mut died) = (false, false);
+        for _ in 0..200 {
+            sim.step([0, 0]);
+            attacked |= matches!(sim.entities[0].entity_type, EntityType::IllagerClan(_, IllagerState::Action));
+            died |= sim.sounds().iter().any(|noise| matches!(noise, Noise::Death));
+        }
+        assert!(attacked && died);
+        assert_eq!(sim.entities.len(), 1);
+        assert_eq!(sim.entities[0].health, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle).stats().health);
+
+        // golems don't disappear when they're beaten
+        spawn_at(&mut sim, EntityType::VillagerClan(VillagerClan::Golem(0, GolemState::Idle)), 10, 11);
+        sim.entities[0].health = u8::MAX;
+        idle(&mut sim, 1000);
+
+        assert_eq!(sim.entities.len(), 2);
+        assert!(matches!(sim.entities[1].entity_type, EntityType::VillagerClan(VillagerClan::Golem(_, GolemState::Broken))));
+        assert!(sim.entities[0].health < u8::MAX);
+    }
+
+    #[test]
+    fn vindicators_hunt_then_march_on_the_village() {
+        let mut sim = empty_sim();
+        place_bell(&mut sim);
+
+        // one villager in sight, one way too far to be noticed
+        spawn_at(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 6, 4);
+        spawn_at(&mut sim, EntityType::VillagerClan(VillagerClan::Farmer(0)), 2, 27);
+        spawn(&mut sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 20, 20);
+
+        idle(&mut sim, 300);
+        assert_eq!(sim.entities.len(), 2);
+        assert!(matches!(sim.entities[0].entity_type, EntityType::VillagerClan(VillagerClan::Farmer(_))));
+
+        // nothing left to hunt, off to the bell
+        let near_bell = |sim: &Simulation| {
+            let vindicator = &sim.entities[1];
+            pathfinding::distance(center_cell(vindicator.position_x, vindicator.position_y), grid_from_vec(15, 15)) <= pathfinding::DIAGONAL_COST
+        };
+        for _ in 0..600 {
+            if near_bell(&sim) {
+                break;
+            }
+            sim.step([0, 0]);
+        }
+        let vindicator = &sim.entities[1];
+        assert!(near_bell(&sim));
+        assert!(vindicator.target.is_none());
+        assert!(vindicator.goal == Some(Goal::Building(grid_from_vec(15, 15))));
+
+        // which doesn't last long
+        idle(&mut sim, 600);
+        assert!(matches!(sim.grid[grid_from_vec(15, 15) as usize], CellState::Bell(BuildingState::Destroyed, _)));
+    }
+
+    #[test]
+    fn pillagers_shoot_from_a_distance() {
+        let pillager = EntityType::IllagerClan(IllagerClan::Pillager, IllagerState::Idle);
+        let villager = EntityType::VillagerClan(VillagerClan::Villager);
+
+        // the villager walks right into the pillager, who backs off and keeps shooting
+        let mut sim = empty_sim();
+        spawn_at(&mut sim, pillager.clone(), 10, 10);
+        spawn_at(&mut sim, villager.clone(), 15, 10);
+        sim.entities[1].goal = Some(Goal::Cell(grid_from_vec(10, 10)));
+
+        let mut shot = false;
+        for _ in 0..600 {
+            sim.step([0, 0]);
+            shot |= !sim.projectiles.is_empty();
+        }
+        assert!(shot);
+        assert_eq!(sim.entities.len(), 1);
+
+        // rocks stop the bolts
+        let mut sim = empty_sim();
+        place(&mut sim, 12, 5, 1, 10, |_| CellState::Rock);
+        spawn_at(&mut sim, pillager, 14, 10);
+        spawn_at(&mut sim, villager.clone(), 10, 10);
+        idle(&mut sim, 600);
+        assert_eq!(sim.entities[1].health, villager.stats().health);
+    }
+
+    #[test]
+    fn pillagers_set_houses_on_fire() {
+        let mut sim = empty_sim();
+        place_houses(&mut sim, 10, 10, BuildingState::Solid);
+        spawn_at(&mut sim, EntityType::IllagerClan(IllagerClan::Pillager, IllagerState::Idle), 14, 11);
+        idle(&mut sim, 120);
+
+        for j in 0..4 {
+            assert!(matches!(sim.grid[grid_from_vec(10 + j % 2, 10 + j / 2) as usize], CellState::House(BuildingState::Burning, ..)));
+        }
+    }
+
+    #[test]
+    fn evokers_cast_fangs_and_summon_vex() {
+        let mut sim = empty_sim();
+
+        // walled off, so only the vex can get to the villager
+        place(&mut sim, 12, 0, 1, GRID_SIZE_Y, |_| CellState::Rock);
+        spawn_at(&mut sim, EntityType::IllagerClan(IllagerClan::Evoker(0), IllagerState::Idle), 7, 10);
+        spawn_at(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 14, 10);
+
+        sim.step([0, 0]);
+        let vex = |sim: &Simulation| sim.entities.iter().filter(|entity| matches!(entity.entity_type, EntityType::IllagerClan(IllagerClan::Vex(_), _))).count();
+        assert_eq!(vex(&sim), VEX_PER_SPELL as usize);
+        assert!(sim.sounds().iter().any(|noise| matches!(noise, Noise::Summon)));
+        assert_eq!(sim.stats().spawned, [0, 0]);
+
+        // they fly over the rocks and get the villager, then vanish after a while
+        idle(&mut sim, VEX_LIFETIME as u32 - 2);
+        assert!(sim.entities.iter().all(|entity| entity.is_illager()));
+        assert_eq!(vex(&sim), VEX_PER_SPELL as usize);
+        idle(&mut sim, 2);
+        assert_eq!(vex(&sim), 0);
+        assert_eq!((sim.stats().spawned, sim.stats().lost), ([0, 0], [1, 0]));
+
+        // close up, it's fangs instead
+        spawn_at(&mut sim, EntityType::VillagerClan(VillagerClan::Farmer(0)), 10, 10);
+        let health = sim.entities[1].health;
+        while !sim.sounds().iter().any(|noise| matches!(noise, Noise::Fangs)) {
+            sim.step([0, 0]);
+        }
+        assert_eq!(sim.entities[1].health, health - FANG_DAMAGE);
+        assert_eq!(vex(&sim), 0);
+    }
+
+    #[test]
+    fn smiths_build_and_repair_golems() {
+        let mut sim = empty_sim();
+        place_bell(&mut sim);
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Smith(0)), 50, 50);
+
+        let golem = |sim: &Simulation| sim.entities.iter().position(|entity| matches!(entity.entity_type, EntityType::VillagerClan(VillagerClan::Golem(..))));
+        idle(&mut sim, GOLEM_WORK as u32 * SMITH_WORK_INTERVAL as u32 + 600);
+
+        let index = golem(&sim).expect("no golem was built");
+        assert_eq!(sim.entities.len(), 2);
+
+        // a golem that got beaten stands back up once the smith is done with it
+        sim.entities[index].health = 0;
+        sim.step([0, 0]);
+        let index = golem(&sim).unwrap();
+        assert!(matches!(sim.entities[index].entity_type, EntityType::VillagerClan(VillagerClan::Golem(_, GolemState::Broken))));
+
+        idle(&mut sim, GOLEM_REPAIR as u32 * SMITH_WORK_INTERVAL as u32 + 300);
+        let index = golem(&sim).unwrap();
+        assert!(sim.entities[index].alive());
+        assert!(matches!(sim.entities[index].entity_type, EntityType::VillagerClan(VillagerClan::Golem(0, GolemState::Idle))));
+    }
+
+    #[test]
+    fn farmers_sell_crops_and_illagers_trample_farms() {
+        let mut sim = empty_sim();
+        place(&mut sim, 10, 10, 2, 1, |j| CellState::Farm(j, FARM_RIPE, 0));
+        place(&mut sim, 20, 20, 2, 1, |j| CellState::Hay(j, 0));
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Farmer(0)), 50, 50);
+
+        let emeralds = sim.emeralds[0];
+        idle(&mut sim, 900);
+
+        // both halves of the farm got harvested and sold, and they're growing back
+        assert_eq!(sim.emeralds[0], emeralds + 2 * EMERALDS_PER_CROP);
+        assert!(matches!(sim.entities[0].entity_type, EntityType::VillagerClan(VillagerClan::Farmer(0))));
+        for j in 0..2 {
+            assert!(matches!(sim.grid[grid_from_vec(10 + j, 10) as usize], CellState::Farm(_, 1..FARM_RIPE, 0)));
+        }
+
+        // a vindicator standing right next to the farm keeps it from growing
+        spawn_at(&mut sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 10, 11);
+        idle(&mut sim, 120);
+        assert!(matches!(sim.grid[grid_from_vec(10, 10) as usize], CellState::Farm(_, 0, 0)));
+    }
+
+    #[test]
+    fn illagers_earn_passive_income_bounties_and_loot() {
+        let mut sim = empty_sim();
+        sim.income.raid_wave_interval = 0;
+        sim.victory.houses_destroyed = false;
+
+        let emeralds = sim.emeralds[1];
+        idle(&mut sim, PASSIVE_INCOME_INTERVAL as u32 + 1);
+        assert_eq!(sim.emeralds[1], emeralds + PASSIVE_INCOME);
+        assert_eq!(sim.next_raid_wave(), None);
+
+        // killing a villager and burning a house down
+        let emeralds = sim.emeralds[1];
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 50, 50);
+        sim.entities[0].health = 0;
+        place_houses(&mut sim, 10, 10, BuildingState::Solid);
+        sim.damage_building(grid_from_vec(11, 11), HOUSE_INTEGRITY);
+        sim.step([0, 0]);
+        assert!(sim.entities.is_empty());
+        assert_eq!(sim.emeralds[1], emeralds + KILL_BOUNTY + HOUSE_LOOT);
+
+        // raid waves come with a bonus
+        sim.income.raid_wave_interval = 10;
+        let emeralds = sim.emeralds[1];
+        while sim.next_raid_wave() != Some(10) {
+            sim.step([0, 0]);
+        }
+        sim.step([0, 0]);
+        assert_eq!(sim.emeralds[1], emeralds + RAID_WAVE_BONUS);
+    }
+
+    #[test]
+    fn buildings_burn_down_and_get_wrecked() {
+        let mut sim = empty_sim();
+        sim.victory.houses_destroyed = false;
+        sim.victory.churches_destroyed = false;
+        place_houses(&mut sim, 10, 10, BuildingState::Solid);
+
+        // the whole house catches fire, no matter which sub-cell got hit
+        sim.ignite(grid_from_vec(11, 11));
+        for j in 0..4 {
+            let cell = sim.grid[grid_from_vec(10 + j % 2, 10 + j / 2) as usize];
+            assert!(matches!(cell, CellState::House(BuildingState::Burning, _, integrity) if integrity == HOUSE_INTEGRITY - 1));
+        }
+
+        idle(&mut sim, HOUSE_INTEGRITY as u32 * BURN_INTERVAL as u32);
+        for j in 0..4 {
+            assert!(matches!(sim.grid[grid_from_vec(10 + j % 2, 10 + j / 2) as usize], CellState::House(BuildingState::Destroyed, _, 0)));
+        }
+
+        // with no bell around, vindicators march on the church and tear it down
+        place(&mut sim, 20, 20, 2, 3, |j| CellState::Church(BuildingState::Solid, j, CHURCH_INTEGRITY));
+        spawn(&mut sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 0, 0);
+        let emeralds = sim.emeralds[1];
+        idle(&mut sim, 1200);
+        assert!(matches!(sim.grid[grid_from_vec(20, 20) as usize], CellState::Church(BuildingState::Destroyed, _, 0)));
+        assert!(sim.emeralds[1] >= emeralds + CHURCH_LOOT);
+        assert!(sim.entities[0].goal.is_none());
+    }
+
+    #[test]
+    fn fire_spreads_until_a_firebreak_and_burns_out() {
+        let mut sim = empty_sim();
+
+        // a row of trees, cut in half by a big rock
+        for x in [2, 4, 8, 10] {
+            place(&mut sim, x, 10, 2, 2, |j| CellState::Tree(j, 0));
+        }
+        place(&mut sim, 6, 10, 2, 2, CellState::BigRock);
+
+        sim.ignite(grid_from_vec(2, 10));
+        idle(&mut sim, 3000);
+
+        // the trees next to the fire burnt down to the ground, the ones behind the rocks are still there
+        for x in 2..6 {
+            assert!(matches!(sim.grid[grid_from_vec(x, 10) as usize], CellState::Empty));
+        }
+        for x in 8..12 {
+            assert!(matches!(sim.grid[grid_from_vec(x, 10) as usize], CellState::Tree(_, 0)));
+        }
+
+        // a growing farm burns too, and passes it on to the hay next to it (it's kept burning until then,
+        // so it doesn't come down to the spread rolls)
+        place(&mut sim, 20, 10, 2, 1, |j| CellState::Farm(j, 1, 0));
+        place(&mut sim, 22, 10, 2, 1, |j| CellState::Hay(j, 0));
+        sim.ignite(grid_from_vec(21, 10));
+        assert!(matches!(sim.grid[grid_from_vec(20, 10) as usize], CellState::Farm(_, 0, FARM_FUEL)));
+        for _ in 0..3000 {
+            if on_fire(&sim.grid[grid_from_vec(22, 10) as usize]) {
+                break;
+            }
+            sim.set_fuel(grid_from_vec(20, 10), FARM_FUEL);
+            sim.step([0, 0]);
+        }
+        assert!(on_fire(&sim.grid[grid_from_vec(22, 10) as usize]));
+
+        idle(&mut sim, 600);
+        for x in 20..24 {
+            assert!(matches!(sim.grid[grid_from_vec(x, 10) as usize], CellState::Empty));
+        }
+    }
+
+    #[test]
+    fn villagers_put_out_fires_and_smiths_rebuild_ruins() {
+        let mut sim = empty_sim();
+        place_houses(&mut sim, 10, 10, BuildingState::Burning);
+        place(&mut sim, 20, 10, 2, 2, |j| CellState::House2(BuildingState::Destroyed, j, 0));
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 9 * CELL_SIZE as u16, 10 * CELL_SIZE as u16);
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Smith(0)), 250, 100);
+
+        // the fire gets put out sooner or later (it's kept going until then, so it's not up to the rolls)
+        let emeralds = sim.emeralds[0];
+        for _ in 0..600 {
+            if !matches!(sim.grid[grid_from_vec(11, 11) as usize], CellState::House(BuildingState::Burning, ..)) {
+                break;
+            }
+            place_houses(&mut sim, 10, 10, BuildingState::Burning);
+            sim.step([0, 0]);
+        }
+        idle(&mut sim, 600);
+
+        // before the house burnt down
+        assert!(matches!(sim.grid[grid_from_vec(11, 11) as usize], CellState::House(BuildingState::Solid, _, 1..)));
+        assert!(sim.entities[0].goal.is_none());
+
+        // and the ruin is standing again, for a price
+        for j in 0..4 {
+            let cell = sim.grid[grid_from_vec(20 + j % 2, 10 + j / 2) as usize];
+            assert!(matches!(cell, CellState::House2(BuildingState::Solid, _, integrity) if integrity == HOUSE_INTEGRITY));
+        }
+        assert_eq!(sim.emeralds[0], emeralds - REBUILD_COST);
+    }
+
+    #[test]
+    fn footer_actions_have_prices_cooldowns_and_rules() {
+        let mut sim = empty_sim();
+        place_bell(&mut sim);
+        place(&mut sim, 10, 20, 2, 2, |j| CellState::Tree(j, 0));
+
+        // barricades go on empty cells, then need to cool down
+        let emeralds = sim.emeralds[0];
+        press_action(&mut sim, 0, 4, 5, 5);
+        press_action(&mut sim, 0, 4, 6, 5);
+        assert!(matches!(sim.grid[grid_from_vec(5, 5) as usize], CellState::Barricade(0)));
+        assert!(matches!(sim.grid[grid_from_vec(6, 5) as usize], CellState::Empty));
+        assert_eq!(sim.emeralds[0], emeralds - BARRICADE);
+
+        // idle villagers gather at the rally flag
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 50, 100);
+        press_action(&mut sim, 0, 3, 20, 10);
+        idle(&mut sim, 500);
+        let villager = &sim.entities[0];
+        assert!(pathfinding::distance(center_cell(villager.position_x, villager.position_y), grid_from_vec(20, 10)) <= pathfinding::DIAGONAL_COST);
+
+        // the bell has to be under the cursor, and it sends the golems running to it
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Golem(0, GolemState::Idle)), 250, 250);
+        press_action(&mut sim, 0, 5, 14, 15);
+        assert!(sim.entities[1].goal.is_none());
+        press_action(&mut sim, 0, 5, 15, 15);
+        assert!(sim.entities[1].goal == Some(Goal::Building(grid_from_vec(15, 15))));
+
+        // torches need an illager close by
+        press_action(&mut sim, 1, 4, 10, 20);
+        assert!(matches!(sim.grid[grid_from_vec(10, 20) as usize], CellState::Tree(_, 0)));
+        spawn(&mut sim, EntityType::IllagerClan(IllagerClan::Pillager, IllagerState::Idle), 70, 200);
+        press_action(&mut sim, 1, 4, 10, 20);
+        assert!(matches!(sim.grid[grid_from_vec(10, 20) as usize], CellState::Tree(_, 1..)));
+
+        // reinforcements come in from the border
+        let (count, emeralds) = (sim.entities.len(), sim.emeralds[1]);
+        press_action(&mut sim, 1, 5, 0, 12);
+        assert_eq!(sim.entities.len(), count + REINFORCEMENT_COUNT as usize);
+        assert_eq!(sim.emeralds[1], emeralds - REINFORCEMENTS);
+    }
+
+    #[test]
+    fn the_bell_sends_villagers_into_hiding_until_all_clear() {
+        let mut sim = empty_sim();
+        place_bell(&mut sim);
+        place_houses(&mut sim, 5, 5, BuildingState::Solid);
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 50, 150);
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Farmer(0)), 150, 40);
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Smith(0)), 250, 250);
+
+        press_action(&mut sim, 0, 5, 15, 15);
+        assert_eq!(sim.alarm, ALARM_DURATION);
+
+        // the smith runs to the bell, the others go inside
+        idle(&mut sim, 300);
+        assert_eq!(sim.sheltered.len(), 2);
+        assert_eq!(sim.entities.len(), 1);
+        assert!(sim.entities[0].goal == Some(Goal::Building(grid_from_vec(15, 15))));
+
+        // a shelter that gets hit is no place to hide
+        sim.damage_building(grid_from_vec(5, 5), 1);
+        sim.step([0, 0]);
+        assert!(sim.sheltered.is_empty());
+        assert_eq!(sim.entities.len(), 3);
+
+        // they'd look for another one, but there's none left, so they stay out
+        sim.extinguish(grid_from_vec(5, 5));
+        idle(&mut sim, 300);
+        while sim.alarm > 0 {
+            sim.step([0, 0]);
+        }
+        sim.step([0, 0]);
+        assert!(sim.sheltered.is_empty());
+        assert_eq!(sim.entities.len(), 3);
+    }
+
+    #[test]
+    fn illagers_can_break_the_bell() {
+        let mut sim = empty_sim();
+        place_bell(&mut sim);
+        spawn(&mut sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 50, 50);
+
+        idle(&mut sim, 600);
+        assert!(matches!(sim.grid[grid_from_vec(15, 15) as usize], CellState::Bell(BuildingState::Destroyed, 0)));
+
+        // a broken bell doesn't ring
+        sim.current_selected_class[0] = 5;
+        sim.cursors[0] = grid_from_vec(15, 15);
+        sim.step([0, 0]);
+        assert!(!sim.action_possible[0]);
+    }
+
+    #[test]
+    fn matches_end_on_the_victory_conditions_and_freeze() {
+        // the last church falling ends it for the villagers, and nothing moves after that
+        let mut sim = empty_sim();
+        place(&mut sim, 20, 20, 2, 3, |j| CellState::Church(BuildingState::Solid, j, CHURCH_INTEGRITY));
+        sim.step([0, 0]);
+        assert_eq!(sim.outcome(), None);
+
+        sim.damage_building(grid_from_vec(20, 20), CHURCH_INTEGRITY);
+        sim.step([0, 0]);
+        assert_eq!(sim.outcome(), Some(Outcome::ChurchesDestroyed));
+        assert_eq!(sim.stats().buildings_burned, 1);
+        assert_eq!(sim.stats().emeralds_earned[1], CHURCH_LOOT as u16);
+
+        let (frame, checksum) = (sim.frame, sim.checksum());
+        sim.step([BUTTON_1, BUTTON_1]);
+        assert_eq!((sim.frame, sim.checksum()), (frame, checksum));
+
+        // holding on long enough
+        let mut sim = empty_sim();
+        sim.victory.survival_frames = 10;
+        for _ in 0..=10 {
+            assert_eq!(sim.outcome(), None);
+            sim.step([0, 0]);
+        }
+        assert_eq!(sim.outcome(), Some(Outcome::Survived));
+
+        // illagers that can't pay for anyone else, with nobody left
+        let mut sim = empty_sim();
+        spawn(&mut sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 50, 50);
+        sim.emeralds[1] = 0;
+        sim.step([0, 0]);
+        assert_eq!(sim.outcome(), None);
+
+        sim.entities[0].health = 0;
+        sim.step([0, 0]);
+        assert_eq!(sim.outcome(), Some(Outcome::IllagersEliminated));
+        assert_eq!(sim.stats().lost, [0, 1]);
+
+        // illagers with emeralds left get a while to send someone new in
+        let mut sim = empty_sim();
+        spawn(&mut sim, EntityType::IllagerClan(IllagerClan::Vindicator, IllagerState::Idle), 50, 50);
+        sim.entities[0].health = 0;
+        idle(&mut sim, ELIMINATION_GRACE as u32 - 2);
+        assert_eq!(sim.outcome(), None);
+        press_action(&mut sim, 1, 0, 0, 12);
+        assert_eq!(sim.outcome(), None);
+        assert!(sim.entities.iter().any(|entity| entity.is_illager()));
+
+        // and lose once that one's gone too and they just sit on their emeralds
+        let emeralds = sim.emeralds[1];
+        sim.entities.iter_mut().for_each(|entity| entity.health = 0);
+        idle(&mut sim, ELIMINATION_GRACE as u32);
+        assert_eq!(sim.outcome(), None);
+        sim.step([0, 0]);
+        assert_eq!(sim.outcome(), Some(Outcome::IllagersEliminated));
+        assert!(emeralds > 0);
+    }
+
+    #[test]
+    fn small_maps_are_walled_in_by_rocks() {
+        let setup = MatchSetup { map_size: MapSize::Small, ..MatchSetup::new(3) };
+        let mut sim = Simulation::with_setup(&setup);
+        let margin = MapSize::Small.margin();
+        for (index, cell) in sim.grid.iter().enumerate() {
+            let (x, y) = vec_from_grid(index as u16);
+            if x < margin || y < margin || x >= GRID_SIZE_X - margin || y >= GRID_SIZE_Y - margin {
+                assert!(matches!(cell, CellState::Rock), "{x} {y}");
+            }
+        }
+
+        // the illager cursor stays inside the playable square
+        assert_eq!(sim.cursors[1], grid_from_vec(margin, margin));
+        for _ in 0..200 {
+            sim.step([0, BUTTON_UP | BUTTON_LEFT]);
+        }
+        assert_eq!(sim.cursors[1], grid_from_vec(margin, margin));
+    }
+
+    #[test]
+    fn the_ai_summons_illagers_on_the_border() {
+        let setup = MatchSetup { mode: Mode::Ai, ..MatchSetup::new(5) };
+        let mut sim = Simulation::with_setup(&setup);
+        sim.victory = VictoryConditions { churches_destroyed: false, houses_destroyed: false, ..Default::default() };
+
+        // the second gamepad is ignored
+        for _ in 0..(AI_THINK_INTERVAL * 20) {
+            sim.step([0, BUTTON_RIGHT]);
+        }
+        assert!(sim.stats().spawned[1] > 0);
+        assert!(sim.emeralds[1] < setup.emeralds[1]);
+        assert!(sim.entities.iter().any(|entity| entity.is_illager()));
+
+        // nobody plays the illagers outside of AI matches
+        let mut sim = Simulation::new(5);
+        idle(&mut sim, AI_THINK_INTERVAL * 20);
+        assert_eq!(sim.stats().spawned[1], 0);
+    }
+
+    #[test]
+    fn units_follow_group_orders() {
+        let mut sim = empty_sim();
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 50, 100);
+        spawn(&mut sim, EntityType::VillagerClan(VillagerClan::Villager), 60, 100);
+
+        // nobody around the cursor, so the flag goes there
+        press_action(&mut sim, 0, 3, 20, 10);
+        assert_eq!(sim.rally[0], Some(grid_from_vec(20, 10)));
+
+        // the group gets picked, then sent somewhere else than the flag
+        press_action(&mut sim, 0, 3, 5, 10);
+        assert!(sim.entities.iter().all(|entity| entity.selected));
+        press_action(&mut sim, 0, 3, 5, 20);
+        assert!(sim.entities.iter().all(|entity| !entity.selected && entity.order.is_some()));
+        assert_eq!(sim.rally[0], Some(grid_from_vec(20, 10)));
+
+        idle(&mut sim, 300);
+        for entity in sim.entities.iter() {
+            let cell = center_cell(entity.position_x, entity.position_y);
+            assert!(entity.order.is_none());
+            assert!(pathfinding::distance(cell, grid_from_vec(5, 20)) <= pathfinding::DIAGONAL_COST * 2);
+        }
+    }
+}
@@ -1,11 +1,22 @@
 use crate::wasm4::*;
 
+#[derive(Clone, Copy)]
 pub enum Noise {
     Ting,
     SixSeven,
     TungTungTungSahour,
     KaboomNoise,
     Burn,
+    Hit,
+    Death,
+    Shoot,
+    Fangs,
+    Summon,
+    Anvil,
+    Harvest,
+    Raid,
+    Splash,
+    Bell,
 }
 
 pub unsafe fn play_me_some_tones______boy(noise: Noise) {
@@ -22,5 +33,27 @@ pub unsafe fn play_me_some_tones______boy(noise: Noise) {
             tone(90, 20, 3, TONE_NOISE);
             tone(90, 20, 6, TONE_TRIANGLE);
         }
+        Noise::Hit => tone(180, 4, 6, TONE_NOISE),
+        Noise::Fangs => {
+            tone(140, 10, 8, TONE_NOISE);
+            tone(220 | (90 << 16), 10, 6, TONE_PULSE2);
+        }
+        Noise::Harvest => tone(500 | (700 << 16), 4, 4, TONE_TRIANGLE),
+        Noise::Anvil => tone(1200, 2, 3, TONE_PULSE1),
+        Noise::Bell => {
+            tone(880, 4 | (50 << 8), 12, TONE_PULSE1 | TONE_MODE3);
+            tone(1320, 2 | (40 << 8), 6, TONE_PULSE2);
+        }
+        Noise::Splash => tone(700 | (200 << 16), 8, 5, TONE_NOISE),
+        Noise::Raid => {
+            tone(110 | (160 << 16), 40, 9, TONE_PULSE2);
+            tone(55, 40, 6, TONE_TRIANGLE);
+        }
+        Noise::Summon => tone(300 | (900 << 16), 18, 6, TONE_TRIANGLE),
+        Noise::Shoot => tone(900 | (500 << 16), 3, 4, TONE_PULSE1),
+        Noise::Death => {
+            tone(400 | (120 << 16), 20, 8, TONE_PULSE2);
+            tone(200, 12, 5, TONE_NOISE);
+        }
     }
 }
\ No newline at end of file
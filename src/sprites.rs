@@ -27,10 +27,16 @@ macro_rules! sprite {
 
 const SHEET: Sprite =  sprite!("../packed/sprite.pak");
 
-pub fn draw_villager_entity(dst_x: i32, dst_y: i32, _type: &VillagerClan) {
-    fn custom_rectangular_sublice(dst_x: i32, dst_y: i32, width: u32, height: u32, src_x: u32, src_y: u32, inside_rect_offset_x: u32, inside_rect_offset_y: u32) {
-        draw_sprite(dst_x + inside_rect_offset_x as i32, dst_y + inside_rect_offset_y as i32, width, height, src_x + inside_rect_offset_x, src_y + inside_rect_offset_y);
-    }
+pub fn draw_villager_entity(dst_x: i32, dst_y: i32, _type: &VillagerClan, flip: bool) {
+    let custom_rectangular_sublice = |dst_x: i32, dst_y: i32, width: u32, height: u32, src_x: u32, src_y: u32, inside_rect_offset_x: u32, inside_rect_offset_y: u32| {
+        if flip {
+            // mirror the piece inside of the cell as well
+            let mirrored_offset_x = CELL_SIZE as u32 - inside_rect_offset_x - width;
+            draw_sprite_with_extra_flags(dst_x + mirrored_offset_x as i32, dst_y + inside_rect_offset_y as i32, width, height, src_x + inside_rect_offset_x, src_y + inside_rect_offset_y, BLIT_FLIP_X);
+        } else {
+            draw_sprite(dst_x + inside_rect_offset_x as i32, dst_y + inside_rect_offset_y as i32, width, height, src_x + inside_rect_offset_x, src_y + inside_rect_offset_y);
+        }
+    };
 
     match _type {
         VillagerClan::Villager => {
@@ -41,7 +47,7 @@ pub fn draw_villager_entity(dst_x: i32, dst_y: i32, _type: &VillagerClan) {
             custom_rectangular_sublice(dst_x, dst_y, 6, 8, 60, 0, 2, 0); // top
             custom_rectangular_sublice(dst_x, dst_y, 4, 2, 60, 0, 3, 8); // bottom
         },
-        VillagerClan::Farmer => {
+        VillagerClan::Farmer(_) => {
             custom_rectangular_sublice(dst_x, dst_y, 6, 1, 50, 0, 2, 0); // hat, top
             custom_rectangular_sublice(dst_x, dst_y, 10, 1, 50, 0, 0, 1); // hat, bottom
 
@@ -76,7 +82,7 @@ pub fn draw_villager_entity(dst_x: i32, dst_y: i32, _type: &VillagerClan) {
     // src x and src y positions inside the sprite sheet
     let (src_x, src_y) = match _type {
         VillagerClan::Villager => (40, 0),
-        VillagerClan::Farmer => (50, 0),
+        VillagerClan::Farmer(_) => (50, 0),
         VillagerClan::Smith { .. } => (60, 0),
         VillagerClan::Golem(_, state) => match state {
             GolemState::Attack => (60, 10),
@@ -88,7 +94,7 @@ pub fn draw_villager_entity(dst_x: i32, dst_y: i32, _type: &VillagerClan) {
     */
 }
 
-pub fn draw_illager_entity(dst_x: i32, dst_y: i32, _type: &IllagerClan, state: &IllagerState) {
+pub fn draw_illager_entity(dst_x: i32, dst_y: i32, _type: &IllagerClan, state: &IllagerState, flip: bool) {
     // src x pos inside the sprite sheet that we will blit from
     let src_x = match _type {
             IllagerClan::Vindicator => 0,
@@ -101,7 +107,12 @@ pub fn draw_illager_entity(dst_x: i32, dst_y: i32, _type: &IllagerClan, state: &
             IllagerState::Idle => 0,
             IllagerState::Action => 10,
         };
-    draw_grid_sprite(src_x, src_y, dst_x, dst_y)
+
+    if flip {
+        draw_sprite_with_extra_flags(dst_x, dst_y, CELL_SIZE as u32, CELL_SIZE as u32, src_x, src_y, BLIT_FLIP_X)
+    } else {
+        draw_grid_sprite(src_x, src_y, dst_x, dst_y)
+    }
 }
 
 // Common functionality for rendering multi-sprite buildings (houses, church, bell, torch pole)
@@ -1,4 +1,4 @@
-use crate::{grid_from_vec, BuildingState, CellState, AREA, GRID_SIZE_X, GRID_SIZE_Y};
+use crate::{grid_from_vec, BuildingState, CellState, AREA, BELL_INTEGRITY, CHURCH_INTEGRITY, FARM_RIPE, GRID_SIZE_X, GRID_SIZE_Y, HOUSE_INTEGRITY};
 
 const MAX_SPAWN_ATTEMPTS: usize = 1000;
 
@@ -30,16 +30,32 @@ fn dist(x: i8, y: i8, center_x: i8, center_y: i8) -> u8 {
 }
 
 // Generate a grid with some interesting terrain
-pub fn generate() -> Box<[CellState; AREA]> {
+// Only the square `margin` cells away from the edges is playable, the rest is filled with rocks
+pub fn generate(rng: &mut fastrand::Rng, margin: u8) -> Box<[CellState; AREA]> {
     let temp: Box<[CellState]> = vec![CellState::Empty; AREA].into_boxed_slice();
     let mut grid: Box<[CellState; AREA]> =
         unsafe { Box::from_raw(Box::into_raw(temp) as *mut [CellState; AREA]) };
+    generate_into(&mut grid, rng, margin);
+    grid
+}
+
+// Same as `generate`, over a board that is already allocated (whatever was on it gets wiped)
+pub fn generate_into(grid: &mut [CellState; AREA], rng: &mut fastrand::Rng, margin: u8) {
+    // rocks go in first so nothing else tries to spawn there
+    for (index, cell) in grid.iter_mut().enumerate() {
+        let (x, y) = crate::vec_from_grid(index as u16);
+        if x < margin || y < margin || x >= GRID_SIZE_X - margin || y >= GRID_SIZE_Y - margin {
+            *cell = CellState::Rock;
+        } else {
+            *cell = CellState::Empty;
+        }
+    }
 
     // NOTE: features defined EARLIER in this array will be PRIORITIZED
     // i.e: we will try spawning the houses FIRST, *then* we will try spawning tree
     let features = [
         Feature {
-            closure: |i| CellState::House(BuildingState::Burning, i),
+            closure: |i| CellState::House(BuildingState::Solid, i, HOUSE_INTEGRITY),
             probability: |x, y| {
                 if x % 3 < 2 {
                     return false;
@@ -53,7 +69,7 @@ pub fn generate() -> Box<[CellState; AREA]> {
             range_to_spawn: [(10, 10), (20, 20)],
         },
         Feature {
-            closure: |i| CellState::House2(BuildingState::Burning, i),
+            closure: |i| CellState::House2(BuildingState::Solid, i, HOUSE_INTEGRITY),
             probability: |x, y| {
                 if x % 3 < 2 {
                     return false;
@@ -67,7 +83,7 @@ pub fn generate() -> Box<[CellState; AREA]> {
             range_to_spawn: [(10, 10), (20, 20)],
         },
         Feature {
-            closure: |i| CellState::Church(BuildingState::Solid, i, 10),
+            closure: |i| CellState::Church(BuildingState::Solid, i, CHURCH_INTEGRITY),
             probability: |x, y| {
                 if x % 3 < 2 {
                     return false;
@@ -91,7 +107,7 @@ pub fn generate() -> Box<[CellState; AREA]> {
             range_to_spawn: [(0, 0), (GRID_SIZE_X, GRID_SIZE_Y)],
         },
         Feature {
-            closure: CellState::Tree,
+            closure: |i| CellState::Tree(i, 0),
             probability: |x, y| {
                 let (x, y) = (x as i8, y as i8);
                 dist(x, y, 15, 15) > 15
@@ -111,7 +127,7 @@ pub fn generate() -> Box<[CellState; AREA]> {
             range_to_spawn: [(0, 0), (GRID_SIZE_X, GRID_SIZE_Y)],
         },
         Feature {
-            closure: |_| CellState::Bell,
+            closure: |_| CellState::Bell(BuildingState::Solid, BELL_INTEGRITY),
             probability: |x, y| {
                 let (x, y) = (x as i8, y as i8);
                 dist(x, y, 15, 15) < 15
@@ -121,7 +137,7 @@ pub fn generate() -> Box<[CellState; AREA]> {
             range_to_spawn: [(13, 13), (17, 17)],
         },
         Feature {
-            closure: CellState::Hay,
+            closure: |i| CellState::Hay(i, 0),
             probability: |x, y| {
                 let (x, y) = (x as i8, y as i8);
                 dist(x, y, 15, 15) < 15
@@ -131,7 +147,7 @@ pub fn generate() -> Box<[CellState; AREA]> {
             range_to_spawn: [(10, 10), (20, 20)],
         },
         Feature {
-            closure: CellState::Farm,
+            closure: |i| CellState::Farm(i, FARM_RIPE, 0),
             probability: |x, y| {
                 let (x, y) = (x as i8, y as i8);
                 dist(x, y, 15, 15) < 15
@@ -151,7 +167,7 @@ pub fn generate() -> Box<[CellState; AREA]> {
             range_to_spawn: [(12, 12), (18, 18)],
         },
         Feature {
-            closure: CellState::Stand,
+            closure: |i| CellState::Stand(i, 0),
             probability: |x, y| {
                 let (x, y) = (x as i8, y as i8);
                 dist(x, y, 15, 15) < 15
@@ -175,8 +191,8 @@ pub fn generate() -> Box<[CellState; AREA]> {
         let max_count = spawn_min_max.1;
 
         'a: for _ in 0..(MAX_SPAWN_ATTEMPTS) {
-            let x = fastrand::u8(range_to_spawn[0].0..(range_to_spawn[1].0));
-            let y = fastrand::u8(range_to_spawn[0].1..(range_to_spawn[1].1));
+            let x = rng.u8(range_to_spawn[0].0..(range_to_spawn[1].0));
+            let y = rng.u8(range_to_spawn[0].1..(range_to_spawn[1].1));
 
             if !probability(x, y) {
                 continue;
@@ -199,13 +215,11 @@ pub fn generate() -> Box<[CellState; AREA]> {
             }
 
             // we have spawned *enough*, but we could spawn more if we wanted to (50/50 random chance to continue)
-            if count > min_count && fastrand::bool() {
+            if count > min_count && rng.bool() {
                 break 'a;
             }
         }
     }
-
-    grid
 }
 
 // Maximum number of cells for one building
@@ -3,6 +3,11 @@
 
 #![allow(unused)]
 
+#[cfg(not(target_arch = "wasm32"))]
+mod font;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod host;
+
 // ┌───────────────────────────────────────────────────────────────────────────┐
 // │                                                                           │
 // │ Platform Constants                                                        │
@@ -11,24 +16,51 @@
 
 pub const SCREEN_SIZE: u32 = 160;
 
+// Bytes of persistent storage
+pub const DISK_SIZE: usize = 1024;
+
 // ┌───────────────────────────────────────────────────────────────────────────┐
 // │                                                                           │
 // │ Memory Addresses                                                          │
 // │                                                                           │
 // └───────────────────────────────────────────────────────────────────────────┘
 
-pub const PALETTE: *mut [u32; 4] = 0x04 as _;
-pub const DRAW_COLORS: *mut u16 = 0x14 as _;
-pub const GAMEPAD1: *const u8 = 0x16 as _;
-pub const GAMEPAD2: *const u8 = 0x17 as _;
-pub const GAMEPAD3: *const u8 = 0x18 as _;
-pub const GAMEPAD4: *const u8 = 0x19 as _;
-pub const MOUSE_X: *const i16 = 0x1a as _;
-pub const MOUSE_Y: *const i16 = 0x1c as _;
-pub const MOUSE_BUTTONS: *const u8 = 0x1e as _;
-pub const SYSTEM_FLAGS: *mut u8 = 0x1f as _;
-pub const NETPLAY: *const u8 = 0x20 as _;
-pub const FRAMEBUFFER: *mut [u8; 6400] = 0xa0 as _;
+#[cfg(target_arch = "wasm32")]
+mod addresses {
+    pub const PALETTE: *mut [u32; 4] = 0x04 as _;
+    pub const DRAW_COLORS: *mut u16 = 0x14 as _;
+    pub const GAMEPAD1: *const u8 = 0x16 as _;
+    pub const GAMEPAD2: *const u8 = 0x17 as _;
+    pub const GAMEPAD3: *const u8 = 0x18 as _;
+    pub const GAMEPAD4: *const u8 = 0x19 as _;
+    pub const MOUSE_X: *const i16 = 0x1a as _;
+    pub const MOUSE_Y: *const i16 = 0x1c as _;
+    pub const MOUSE_BUTTONS: *const u8 = 0x1e as _;
+    pub const SYSTEM_FLAGS: *mut u8 = 0x1f as _;
+    pub const NETPLAY: *const u8 = 0x20 as _;
+    pub const FRAMEBUFFER: *mut [u8; 6400] = 0xa0 as _;
+}
+
+// Same memory map, but relative to the in-memory copy kept by the host backend
+#[cfg(not(target_arch = "wasm32"))]
+mod addresses {
+    use super::host::address;
+
+    pub const PALETTE: *mut [u32; 4] = address(0x04);
+    pub const DRAW_COLORS: *mut u16 = address(0x14);
+    pub const GAMEPAD1: *const u8 = address(0x16);
+    pub const GAMEPAD2: *const u8 = address(0x17);
+    pub const GAMEPAD3: *const u8 = address(0x18);
+    pub const GAMEPAD4: *const u8 = address(0x19);
+    pub const MOUSE_X: *const i16 = address(0x1a);
+    pub const MOUSE_Y: *const i16 = address(0x1c);
+    pub const MOUSE_BUTTONS: *const u8 = address(0x1e);
+    pub const SYSTEM_FLAGS: *mut u8 = address(0x1f);
+    pub const NETPLAY: *const u8 = address(0x20);
+    pub const FRAMEBUFFER: *mut [u8; 6400] = address(0xa0);
+}
+
+pub use addresses::*;
 
 pub const BUTTON_1: u8 = 1;
 pub const BUTTON_2: u8 = 2;
@@ -52,8 +84,12 @@ pub const SYSTEM_HIDE_GAMEPAD_OVERLAY: u8 = 2;
 
 /// Copies pixels to the framebuffer.
 pub fn blit(sprite: &[u8], x: i32, y: i32, width: u32, height: u32, flags: u32) {
+    #[cfg(target_arch = "wasm32")]
     unsafe { extern_blit(sprite.as_ptr(), x, y, width, height, flags) }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::blit(sprite, x, y, width, height, flags)
 }
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "blit"]
     fn extern_blit(sprite: *const u8, x: i32, y: i32, width: u32, height: u32, flags: u32);
@@ -72,6 +108,7 @@ pub fn blit_sub(
     stride: u32,
     flags: u32,
 ) {
+    #[cfg(target_arch = "wasm32")]
     unsafe {
         extern_blit_sub(
             sprite.as_ptr(),
@@ -85,7 +122,10 @@ pub fn blit_sub(
             flags,
         )
     }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::blit_sub(sprite, x, y, width, height, src_x, src_y, stride, flags)
 }
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "blitSub"]
     fn extern_blit_sub(
@@ -109,8 +149,12 @@ pub const BLIT_ROTATE: u32 = 8;
 
 /// Draws a line between two points.
 pub fn line(x1: i32, y1: i32, x2: i32, y2: i32) {
+    #[cfg(target_arch = "wasm32")]
     unsafe { extern_line(x1, y1, x2, y2) }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::line(x1, y1, x2, y2)
 }
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "line"]
     fn extern_line(x1: i32, y1: i32, x2: i32, y2: i32);
@@ -118,8 +162,12 @@ extern "C" {
 
 /// Draws an oval (or circle).
 pub fn oval(x: i32, y: i32, width: u32, height: u32) {
+    #[cfg(target_arch = "wasm32")]
     unsafe { extern_oval(x, y, width, height) }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::oval(x, y, width, height)
 }
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "oval"]
     fn extern_oval(x: i32, y: i32, width: u32, height: u32);
@@ -127,8 +175,12 @@ extern "C" {
 
 /// Draws a rectangle.
 pub fn rect(x: i32, y: i32, width: u32, height: u32) {
+    #[cfg(target_arch = "wasm32")]
     unsafe { extern_rect(x, y, width, height) }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::rect(x, y, width, height)
 }
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "rect"]
     fn extern_rect(x: i32, y: i32, width: u32, height: u32);
@@ -137,8 +189,12 @@ extern "C" {
 /// Draws text using the built-in system font.
 pub fn text<T: AsRef<[u8]>>(text: T, x: i32, y: i32) {
     let text_ref = text.as_ref();
+    #[cfg(target_arch = "wasm32")]
     unsafe { extern_text(text_ref.as_ptr(), text_ref.len(), x, y) }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::text(text_ref, x, y)
 }
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "textUtf8"]
     fn extern_text(text: *const u8, length: usize, x: i32, y: i32);
@@ -146,11 +202,15 @@ extern "C" {
 
 /// Draws a vertical line
 pub fn vline(x: i32, y: i32, len: u32) {
+    #[cfg(target_arch = "wasm32")]
     unsafe {
         extern_vline(x, y, len);
     }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::vline(x, y, len);
 }
 
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "vline"]
     fn extern_vline(x: i32, y: i32, len: u32);
@@ -158,11 +218,15 @@ extern "C" {
 
 /// Draws a horizontal line
 pub fn hline(x: i32, y: i32, len: u32) {
+    #[cfg(target_arch = "wasm32")]
     unsafe {
         extern_hline(x, y, len);
     }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::hline(x, y, len);
 }
 
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "hline"]
     fn extern_hline(x: i32, y: i32, len: u32);
@@ -176,8 +240,12 @@ extern "C" {
 
 /// Plays a sound tone.
 pub fn tone(frequency: u32, duration: u32, volume: u32, flags: u32) {
+    #[cfg(target_arch = "wasm32")]
     unsafe { extern_tone(frequency, duration, volume, flags) }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::tone(frequency, duration, volume, flags)
 }
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "tone"]
     fn extern_tone(frequency: u32, duration: u32, volume: u32, flags: u32);
@@ -200,6 +268,7 @@ pub const TONE_PAN_RIGHT: u32 = 32;
 // │                                                                           │
 // └───────────────────────────────────────────────────────────────────────────┘
 
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     /// Reads up to `size` bytes from persistent storage into the pointer `dest`.
     pub fn diskr(dest: *mut u8, size: u32) -> u32;
@@ -208,6 +277,9 @@ extern "C" {
     pub fn diskw(src: *const u8, size: u32) -> u32;
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use host::{diskr, diskw};
+
 // ┌───────────────────────────────────────────────────────────────────────────┐
 // │                                                                           │
 // │ Other Functions                                                           │
@@ -217,8 +289,12 @@ extern "C" {
 /// Prints a message to the debug console.
 pub fn trace<T: AsRef<str>>(text: T) {
     let text_ref = text.as_ref();
+    #[cfg(target_arch = "wasm32")]
     unsafe { extern_trace(text_ref.as_ptr(), text_ref.len()) }
+    #[cfg(not(target_arch = "wasm32"))]
+    host::trace(text_ref)
 }
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     #[link_name = "traceUtf8"]
     fn extern_trace(trace: *const u8, length: usize);
@@ -0,0 +1,230 @@
+// Built-in 8x8 system font, one byte per row and one bit per pixel (MSB is the leftmost pixel).
+// Covers characters 32..=255, same layout as the runtime. Bits are inverted: glyph pixels are 0,
+// so text is drawn with the first draw color and the background with the second one.
+// Printable ASCII glyphs come from the public domain X11 "5x8" fixed font, the rest are blank.
+pub const FONT: [u8; 1792] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // ' '
+    0xff, 0xef, 0xef, 0xef, 0xef, 0xff, 0xef, 0xff, // '!'
+    0xff, 0xd7, 0xd7, 0xd7, 0xff, 0xff, 0xff, 0xff, // '"'
+    0xd7, 0xd7, 0x83, 0xd7, 0x83, 0xd7, 0xd7, 0xff, // '#'
+    0xef, 0xc7, 0xaf, 0xc7, 0xeb, 0xc7, 0xef, 0xff, // '$'
+    0xff, 0xdf, 0xd7, 0xef, 0xd7, 0xf7, 0xff, 0xff, // '%'
+    0xdf, 0xaf, 0xaf, 0xdf, 0xaf, 0xaf, 0xd7, 0xff, // '&'
+    0xff, 0xef, 0xef, 0xef, 0xff, 0xff, 0xff, 0xff, // "'"
+    0xff, 0xef, 0xdf, 0xdf, 0xdf, 0xdf, 0xef, 0xff, // '('
+    0xff, 0xdf, 0xef, 0xef, 0xef, 0xef, 0xdf, 0xff, // ')'
+    0xff, 0xff, 0xb7, 0xcf, 0x87, 0xcf, 0xb7, 0xff, // '*'
+    0xff, 0xff, 0xef, 0xef, 0x83, 0xef, 0xef, 0xff, // '+'
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xe7, 0xef, 0xdf, // ','
+    0xff, 0xff, 0xff, 0xff, 0x87, 0xff, 0xff, 0xff, // '-'
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xef, 0xc7, 0xef, // '.'
+    0xff, 0xf7, 0xf7, 0xef, 0xdf, 0xbf, 0xbf, 0xff, // '/'
+    0xff, 0xef, 0xd7, 0xd7, 0xd7, 0xd7, 0xef, 0xff, // '0'
+    0xff, 0xef, 0xcf, 0xef, 0xef, 0xef, 0xc7, 0xff, // '1'
+    0xff, 0xcf, 0xb7, 0xf7, 0xcf, 0xbf, 0x87, 0xff, // '2'
+    0xff, 0x87, 0xef, 0xcf, 0xf7, 0xb7, 0xcf, 0xff, // '3'
+    0xff, 0xef, 0xcf, 0xaf, 0x87, 0xef, 0xef, 0xff, // '4'
+    0xff, 0x87, 0xbf, 0x8f, 0xf7, 0xb7, 0xcf, 0xff, // '5'
+    0xff, 0xcf, 0xbf, 0x8f, 0xb7, 0xb7, 0xcf, 0xff, // '6'
+    0xff, 0x87, 0xf7, 0xef, 0xef, 0xdf, 0xdf, 0xff, // '7'
+    0xff, 0xcf, 0xb7, 0xcf, 0xb7, 0xb7, 0xcf, 0xff, // '8'
+    0xff, 0xcf, 0xb7, 0xb7, 0xc7, 0xf7, 0xcf, 0xff, // '9'
+    0xff, 0xff, 0xcf, 0xcf, 0xff, 0xcf, 0xcf, 0xff, // ':'
+    0xff, 0xff, 0xe7, 0xe7, 0xff, 0xe7, 0xef, 0xdf, // ';'
+    0xff, 0xf7, 0xef, 0xdf, 0xdf, 0xef, 0xf7, 0xff, // '<'
+    0xff, 0xff, 0xff, 0x87, 0xff, 0x87, 0xff, 0xff, // '='
+    0xff, 0xdf, 0xef, 0xf7, 0xf7, 0xef, 0xdf, 0xff, // '>'
+    0xff, 0xef, 0xd7, 0xf7, 0xef, 0xff, 0xef, 0xff, // '?'
+    0xe7, 0xdb, 0xb3, 0xab, 0xab, 0xb7, 0xdf, 0xe7, // '@'
+    0xff, 0xcf, 0xb7, 0xb7, 0x87, 0xb7, 0xb7, 0xff, // 'A'
+    0xff, 0x8f, 0xb7, 0x8f, 0xb7, 0xb7, 0x8f, 0xff, // 'B'
+    0xff, 0xcf, 0xb7, 0xbf, 0xbf, 0xb7, 0xcf, 0xff, // 'C'
+    0xff, 0x8f, 0xb7, 0xb7, 0xb7, 0xb7, 0x8f, 0xff, // 'D'
+    0xff, 0x87, 0xbf, 0x8f, 0xbf, 0xbf, 0x87, 0xff, // 'E'
+    0xff, 0x87, 0xbf, 0x8f, 0xbf, 0xbf, 0xbf, 0xff, // 'F'
+    0xff, 0xcf, 0xb7, 0xbf, 0xa7, 0xb7, 0xcf, 0xff, // 'G'
+    0xff, 0xb7, 0xb7, 0x87, 0xb7, 0xb7, 0xb7, 0xff, // 'H'
+    0xff, 0xc7, 0xef, 0xef, 0xef, 0xef, 0xc7, 0xff, // 'I'
+    0xff, 0xc7, 0xef, 0xef, 0xef, 0xaf, 0xdf, 0xff, // 'J'
+    0xff, 0xb7, 0xaf, 0x9f, 0xaf, 0xaf, 0xb7, 0xff, // 'K'
+    0xff, 0xbf, 0xbf, 0xbf, 0xbf, 0xbf, 0x87, 0xff, // 'L'
+    0xff, 0xb7, 0x87, 0x87, 0xb7, 0xb7, 0xb7, 0xff, // 'M'
+    0xff, 0xb7, 0x97, 0x87, 0xa7, 0xa7, 0xb7, 0xff, // 'N'
+    0xff, 0xcf, 0xb7, 0xb7, 0xb7, 0xb7, 0xcf, 0xff, // 'O'
+    0xff, 0x8f, 0xb7, 0xb7, 0x8f, 0xbf, 0xbf, 0xff, // 'P'
+    0xff, 0xcf, 0xb7, 0xb7, 0x97, 0xa7, 0xcf, 0xf7, // 'Q'
+    0xff, 0x8f, 0xb7, 0xb7, 0x8f, 0xb7, 0xb7, 0xff, // 'R'
+    0xff, 0xcf, 0xb7, 0xdf, 0xef, 0xb7, 0xcf, 0xff, // 'S'
+    0xff, 0xc7, 0xef, 0xef, 0xef, 0xef, 0xef, 0xff, // 'T'
+    0xff, 0xb7, 0xb7, 0xb7, 0xb7, 0xb7, 0xcf, 0xff, // 'U'
+    0xff, 0xb7, 0xb7, 0xb7, 0xb7, 0xcf, 0xcf, 0xff, // 'V'
+    0xff, 0xb7, 0xb7, 0xb7, 0x87, 0x87, 0xb7, 0xff, // 'W'
+    0xff, 0xb7, 0xb7, 0xcf, 0xcf, 0xb7, 0xb7, 0xff, // 'X'
+    0xff, 0xbb, 0xbb, 0xd7, 0xef, 0xef, 0xef, 0xff, // 'Y'
+    0xff, 0x87, 0xf7, 0xef, 0xdf, 0xbf, 0x87, 0xff, // 'Z'
+    0xff, 0xc7, 0xdf, 0xdf, 0xdf, 0xdf, 0xc7, 0xff, // '['
+    0xff, 0xbf, 0xbf, 0xdf, 0xef, 0xf7, 0xf7, 0xff, // '\\'
+    0xff, 0xc7, 0xf7, 0xf7, 0xf7, 0xf7, 0xc7, 0xff, // ']'
+    0xff, 0xef, 0xd7, 0xff, 0xff, 0xff, 0xff, 0xff, // '^'
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x87, // '_'
+    0xff, 0xdf, 0xef, 0xff, 0xff, 0xff, 0xff, 0xff, // '`'
+    0xff, 0xff, 0xff, 0xc7, 0xb7, 0xb7, 0xc7, 0xff, // 'a'
+    0xff, 0xbf, 0xbf, 0x8f, 0xb7, 0xb7, 0x8f, 0xff, // 'b'
+    0xff, 0xff, 0xff, 0xe7, 0xdf, 0xdf, 0xe7, 0xff, // 'c'
+    0xff, 0xf7, 0xf7, 0xc7, 0xb7, 0xb7, 0xc7, 0xff, // 'd'
+    0xff, 0xff, 0xff, 0xcf, 0xa7, 0x9f, 0xcf, 0xff, // 'e'
+    0xff, 0xef, 0xd7, 0xdf, 0x8f, 0xdf, 0xdf, 0xff, // 'f'
+    0xff, 0xff, 0xff, 0xcf, 0xb7, 0xc7, 0xf7, 0xcf, // 'g'
+    0xff, 0xbf, 0xbf, 0x8f, 0xb7, 0xb7, 0xb7, 0xff, // 'h'
+    0xff, 0xef, 0xff, 0xcf, 0xef, 0xef, 0xc7, 0xff, // 'i'
+    0xff, 0xf7, 0xff, 0xf7, 0xf7, 0xf7, 0xd7, 0xef, // 'j'
+    0xff, 0xbf, 0xbf, 0xb7, 0x8f, 0xb7, 0xb7, 0xff, // 'k'
+    0xff, 0xcf, 0xef, 0xef, 0xef, 0xef, 0xc7, 0xff, // 'l'
+    0xff, 0xff, 0xff, 0x97, 0xab, 0xab, 0xab, 0xff, // 'm'
+    0xff, 0xff, 0xff, 0x8f, 0xb7, 0xb7, 0xb7, 0xff, // 'n'
+    0xff, 0xff, 0xff, 0xcf, 0xb7, 0xb7, 0xcf, 0xff, // 'o'
+    0xff, 0xff, 0xff, 0x8f, 0xb7, 0x8f, 0xbf, 0xbf, // 'p'
+    0xff, 0xff, 0xff, 0xc7, 0xb7, 0xc7, 0xf7, 0xf7, // 'q'
+    0xff, 0xff, 0xff, 0xaf, 0x97, 0xbf, 0xbf, 0xff, // 'r'
+    0xff, 0xff, 0xff, 0xe7, 0xcf, 0xf7, 0xcf, 0xff, // 's'
+    0xff, 0xdf, 0xdf, 0x8f, 0xdf, 0xd7, 0xef, 0xff, // 't'
+    0xff, 0xff, 0xff, 0xb7, 0xb7, 0xb7, 0xc7, 0xff, // 'u'
+    0xff, 0xff, 0xff, 0xd7, 0xd7, 0xd7, 0xef, 0xff, // 'v'
+    0xff, 0xff, 0xff, 0xbb, 0xab, 0xab, 0xd7, 0xff, // 'w'
+    0xff, 0xff, 0xff, 0xb7, 0xcf, 0xcf, 0xb7, 0xff, // 'x'
+    0xff, 0xff, 0xff, 0xb7, 0xb7, 0xc7, 0xb7, 0xcf, // 'y'
+    0xff, 0xff, 0xff, 0x87, 0xef, 0xdf, 0x87, 0xff, // 'z'
+    0xe7, 0xdf, 0xef, 0x9f, 0xef, 0xdf, 0xe7, 0xff, // '{'
+    0xff, 0xef, 0xef, 0xef, 0xef, 0xef, 0xef, 0xff, // '|'
+    0x9f, 0xef, 0xdf, 0xe7, 0xdf, 0xef, 0x9f, 0xff, // '}'
+    0xff, 0xd7, 0xaf, 0xff, 0xff, 0xff, 0xff, 0xff, // '~'
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x7f
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x80
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x81
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x82
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x83
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x84
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x85
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x86
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x87
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x88
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x89
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x8a
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x8b
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x8c
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x8d
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x8e
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x8f
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x90
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x91
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x92
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x93
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x94
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x95
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x96
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x97
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x98
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x99
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x9a
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x9b
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x9c
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x9d
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x9e
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0x9f
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa0
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa1
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa2
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa3
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa4
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa5
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa6
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa7
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa8
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xa9
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xaa
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xab
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xac
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xad
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xae
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xaf
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb0
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb1
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb2
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb3
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb4
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb5
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb6
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb7
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb8
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xb9
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xba
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xbb
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xbc
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xbd
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xbe
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xbf
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc0
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc1
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc2
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc3
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc4
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc5
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc6
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc7
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc8
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xc9
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xca
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xcb
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xcc
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xcd
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xce
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xcf
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd0
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd1
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd2
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd3
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd4
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd5
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd6
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd7
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd8
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xd9
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xda
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xdb
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xdc
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xdd
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xde
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xdf
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe0
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe1
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe2
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe3
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe4
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe5
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe6
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe7
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe8
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xe9
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xea
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xeb
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xec
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xed
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xee
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xef
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf0
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf1
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf2
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf3
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf4
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf5
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf6
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf7
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf8
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xf9
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xfa
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xfb
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xfc
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xfd
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xfe
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // 0xff
+];
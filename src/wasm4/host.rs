@@ -0,0 +1,440 @@
+//
+// Native stand-in for the WASM-4 runtime, so the cart can run on the host and under `cargo test`.
+// Keeps the same memory map as the console (palette, draw colors, gamepads, framebuffer, ...)
+// plus the 1024 bytes of persistent disk storage.
+
+use super::font::FONT;
+use super::*;
+
+// Everything up to (and including) the framebuffer
+const MEMORY_SIZE: usize = 0xa0 + 6400;
+
+// Power-on values, as set by the real runtime
+const DEFAULT_PALETTE: [u32; 4] = [0xe0f8cf, 0x86c06c, 0x306850, 0x071821];
+const DEFAULT_DRAW_COLORS: u16 = 0x1203;
+
+// Aligned so the u16/u32 registers inside of it are aligned too
+#[repr(C, align(8))]
+struct Memory([u8; MEMORY_SIZE]);
+
+static mut MEMORY: Memory = Memory([0; MEMORY_SIZE]);
+static mut DISK: [u8; DISK_SIZE] = [0; DISK_SIZE];
+static mut DISK_LEN: usize = 0;
+
+// Pointer to the given WASM-4 address inside of the host memory
+pub(super) const fn address<T>(offset: usize) -> *mut T {
+    unsafe { (&raw mut MEMORY.0).cast::<u8>().add(offset).cast() }
+}
+
+/// Restores the memory to the state the runtime boots with. Persistent storage is kept.
+pub fn reset() {
+    unsafe {
+        *address::<[u8; MEMORY_SIZE]>(0) = [0; MEMORY_SIZE];
+        *PALETTE = DEFAULT_PALETTE;
+        *DRAW_COLORS = DEFAULT_DRAW_COLORS;
+    }
+}
+
+/// Must be called before every `update`, like the runtime does. Clears the framebuffer unless
+/// `SYSTEM_PRESERVE_FRAMEBUFFER` is set.
+pub fn begin_frame() {
+    unsafe {
+        if *SYSTEM_FLAGS & SYSTEM_PRESERVE_FRAMEBUFFER == 0 {
+            *FRAMEBUFFER = [0; 6400];
+        }
+    }
+}
+
+/// Sets the buttons currently held by the given player (0..4).
+pub fn set_gamepad(player: usize, buttons: u8) {
+    assert!(player < 4, "WASM-4 only has 4 gamepads");
+    unsafe { *address::<u8>(0x16 + player) = buttons }
+}
+
+/// Sets the raw netplay register (bit 2 = netplay active, bits 0..2 = local player index).
+pub fn set_netplay(value: u8) {
+    unsafe { *address::<u8>(0x20) = value }
+}
+
+/// Current palette colors as 0xRRGGBB.
+pub fn palette() -> [u32; 4] {
+    unsafe { *PALETTE }
+}
+
+/// Copy of the 160x160 2bpp framebuffer (4 pixels per byte, leftmost pixel in the low bits).
+pub fn framebuffer() -> [u8; 6400] {
+    unsafe { *FRAMEBUFFER }
+}
+
+/// Palette index (0..4) of a single framebuffer pixel.
+pub fn pixel(x: u32, y: u32) -> u8 {
+    let index = (y * SCREEN_SIZE + x) as usize;
+    let byte = unsafe { (*FRAMEBUFFER)[index / 4] };
+    (byte >> ((index % 4) * 2)) & 0b11
+}
+
+/// Current contents of the persistent storage.
+pub fn disk() -> Vec<u8> {
+    unsafe { DISK[..DISK_LEN].to_vec() }
+}
+
+/// Replaces the contents of the persistent storage. Anything past `DISK_SIZE` is dropped.
+pub fn set_disk(bytes: &[u8]) {
+    let len = bytes.len().min(DISK_SIZE);
+    unsafe {
+        DISK[..len].copy_from_slice(&bytes[..len]);
+        DISK_LEN = len;
+    }
+}
+
+/// Reads up to `size` bytes from persistent storage into the pointer `dest`.
+///
+/// # Safety
+/// `dest` must be valid for `size` bytes of writes
+pub unsafe fn diskr(dest: *mut u8, size: u32) -> u32 {
+    let len = (size as usize).min(DISK_LEN);
+    std::ptr::copy_nonoverlapping((&raw const DISK).cast::<u8>(), dest, len);
+    len as u32
+}
+
+/// Writes up to `size` bytes from the pointer `src` into persistent storage.
+///
+/// # Safety
+/// `src` must be valid for `size` bytes of reads
+pub unsafe fn diskw(src: *const u8, size: u32) -> u32 {
+    let len = (size as usize).min(DISK_SIZE);
+    std::ptr::copy_nonoverlapping(src, (&raw mut DISK).cast::<u8>(), len);
+    DISK_LEN = len;
+    len as u32
+}
+
+// ┌───────────────────────────────────────────────────────────────────────────┐
+// │                                                                           │
+// │ Rasterizer (mirrors the reference runtime's framebuffer.c)                │
+// │                                                                           │
+// └───────────────────────────────────────────────────────────────────────────┘
+
+const WIDTH: i32 = SCREEN_SIZE as i32;
+const HEIGHT: i32 = SCREEN_SIZE as i32;
+
+// Returns the first two draw colors (nibbles 0 and 1)
+fn draw_colors_01() -> (u8, u8) {
+    let colors = unsafe { *DRAW_COLORS };
+    ((colors & 0xf) as u8, ((colors >> 4) & 0xf) as u8)
+}
+
+// Caller makes sure x and y are on screen
+fn draw_point(color: u8, x: i32, y: i32) {
+    let index = (WIDTH * y + x) as usize;
+    let shift = (index % 4) * 2;
+    let framebuffer = unsafe { &mut *FRAMEBUFFER };
+    let byte = &mut framebuffer[index / 4];
+    *byte = (color << shift) | (*byte & !(0b11 << shift));
+}
+
+fn draw_point_unclipped(color: u8, x: i32, y: i32) {
+    if (0..WIDTH).contains(&x) && (0..HEIGHT).contains(&y) {
+        draw_point(color, x, y);
+    }
+}
+
+// Draws from start_x up to (excluding) end_x, caller makes sure the range is on screen
+fn draw_hline_fast(color: u8, start_x: i32, y: i32, end_x: i32) {
+    for x in start_x..end_x {
+        draw_point(color, x, y);
+    }
+}
+
+fn draw_hline_unclipped(color: u8, start_x: i32, y: i32, end_x: i32) {
+    if (0..HEIGHT).contains(&y) {
+        let start_x = start_x.max(0);
+        let end_x = end_x.min(WIDTH);
+        if start_x < end_x {
+            draw_hline_fast(color, start_x, y, end_x);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn blit_sub(
+    sprite: &[u8],
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    src_x: u32,
+    src_y: u32,
+    stride: u32,
+    flags: u32,
+) {
+    let colors = unsafe { *DRAW_COLORS };
+    let bpp2 = flags & BLIT_2BPP != 0;
+    let mut flip_x = flags & BLIT_FLIP_X != 0;
+    let flip_y = flags & BLIT_FLIP_Y != 0;
+    let rotate = flags & BLIT_ROTATE != 0;
+    let (width, height) = (width as i32, height as i32);
+
+    // Clip the rectangle to the screen (in sprite space)
+    let (clip_x_min, clip_y_min, clip_x_max, clip_y_max) = if rotate {
+        flip_x = !flip_x;
+        (
+            y.max(0) - y,
+            x.max(0) - x,
+            width.min(HEIGHT - y),
+            height.min(WIDTH - x),
+        )
+    } else {
+        (
+            x.max(0) - x,
+            y.max(0) - y,
+            width.min(WIDTH - x),
+            height.min(HEIGHT - y),
+        )
+    };
+
+    for local_y in clip_y_min..clip_y_max {
+        for local_x in clip_x_min..clip_x_max {
+            // Target coords on the screen
+            let (tx, ty) = if rotate {
+                (x + local_y, y + local_x)
+            } else {
+                (x + local_x, y + local_y)
+            };
+
+            // Source coords inside the sprite
+            let sx = src_x as i32 + if flip_x { width - local_x - 1 } else { local_x };
+            let sy = src_y as i32 + if flip_y { height - local_y - 1 } else { local_y };
+
+            // Sample the sprite to get a color index
+            let bit_index = (sy * stride as i32 + sx) as usize;
+            let color_index = if bpp2 {
+                let shift = 6 - (bit_index % 4) * 2;
+                (sprite[bit_index / 4] >> shift) & 0b11
+            } else {
+                let shift = 7 - bit_index % 8;
+                (sprite[bit_index / 8] >> shift) & 0b1
+            };
+
+            // Go through the draw colors, 0 is transparent
+            let draw_color = ((colors >> (color_index * 4)) & 0xf) as u8;
+            if draw_color != 0 {
+                draw_point((draw_color - 1) & 0b11, tx, ty);
+            }
+        }
+    }
+}
+
+pub(super) fn blit(sprite: &[u8], x: i32, y: i32, width: u32, height: u32, flags: u32) {
+    blit_sub(sprite, x, y, width, height, 0, 0, width, flags)
+}
+
+// Bresenham, always walking downwards
+pub(super) fn line(x1: i32, y1: i32, x2: i32, y2: i32) {
+    let (dc0, _) = draw_colors_01();
+    if dc0 == 0 {
+        return;
+    }
+    let stroke = (dc0 - 1) & 0b11;
+
+    let (mut x1, mut y1, x2, y2) = if y1 > y2 {
+        (x2, y2, x1, y1)
+    } else {
+        (x1, y1, x2, y2)
+    };
+
+    let dx = (x2 - x1).abs();
+    let sx = if x1 < x2 { 1 } else { -1 };
+    let dy = y2 - y1;
+    let mut err = if dx > dy { dx } else { -dy } / 2;
+
+    loop {
+        draw_point_unclipped(stroke, x1, y1);
+        if x1 == x2 && y1 == y2 {
+            break;
+        }
+
+        let e2 = err;
+        if e2 > -dx {
+            err -= dy;
+            x1 += sx;
+        }
+        if e2 < dy {
+            err += dx;
+            y1 += 1;
+        }
+    }
+}
+
+// Midpoint ellipse, drawing the outline with the second draw color and the fill with the first one
+pub(super) fn oval(x: i32, y: i32, width: u32, height: u32) {
+    let (dc0, dc1) = draw_colors_01();
+    let stroke = dc1.wrapping_sub(1) & 0b11;
+    let fill = dc0.wrapping_sub(1) & 0b11;
+    let (width, height) = (width as i64, height as i64);
+
+    let mut a = width - 1;
+    let b = height - 1;
+    let mut b1 = b % 2; // Compensates for precision loss when dividing
+
+    let mut north = y as i64 + height / 2; // Precision loss here
+    let mut west = x as i64;
+    let mut east = x as i64 + width - 1;
+    let mut south = north - b1; // Compensation here, moves the bottom line up by one for even heights
+
+    // Error increments
+    let mut dx = 4 * (1 - a) * b * b;
+    let mut dy = 4 * (b1 + 1) * a * a;
+
+    // Error of 1 step
+    let mut err = dx + dy + b1 * a * a;
+
+    a = 8 * a * a;
+    b1 = 8 * b * b;
+
+    let point = |x: i64, y: i64| {
+        if dc1 != 0 {
+            draw_point_unclipped(stroke, x as i32, y as i32);
+        }
+    };
+
+    loop {
+        point(east, north);
+        point(west, north);
+        point(west, south);
+        point(east, south);
+
+        let start = west + 1;
+        let len = east - start;
+        if dc0 != 0 && len > 0 {
+            draw_hline_unclipped(fill, start as i32, north as i32, east as i32);
+            draw_hline_unclipped(fill, start as i32, south as i32, east as i32);
+        }
+
+        let err2 = 2 * err;
+        if err2 <= dy {
+            // Move vertical scan
+            north += 1;
+            south -= 1;
+            dy += a;
+            err += dy;
+        }
+        if err2 >= dx || err2 > dy {
+            // Move horizontal scan
+            west += 1;
+            east -= 1;
+            dx += b1;
+            err += dx;
+        }
+
+        if west > east {
+            break;
+        }
+    }
+
+    // Make sure north and south have moved the entire way so the top and bottom aren't missing
+    while north - south < height {
+        point(west - 1, north);
+        point(east + 1, north);
+        north += 1;
+        point(west - 1, south);
+        point(east + 1, south);
+        south -= 1;
+    }
+}
+
+// Fill with the first draw color, outline with the second one
+pub(super) fn rect(x: i32, y: i32, width: u32, height: u32) {
+    let start_x = x.max(0);
+    let start_y = y.max(0);
+    let end_x_unclamped = x + width as i32;
+    let end_y_unclamped = y + height as i32;
+    let end_x = end_x_unclamped.min(WIDTH);
+    let end_y = end_y_unclamped.min(HEIGHT);
+
+    let (dc0, dc1) = draw_colors_01();
+
+    if dc0 != 0 {
+        let fill = (dc0 - 1) & 0b11;
+        for yy in start_y..end_y {
+            draw_hline_fast(fill, start_x, yy, end_x);
+        }
+    }
+
+    if dc1 != 0 {
+        let stroke = (dc1 - 1) & 0b11;
+
+        // Left edge
+        if (0..WIDTH).contains(&x) {
+            for yy in start_y..end_y {
+                draw_point(stroke, x, yy);
+            }
+        }
+
+        // Right edge
+        if end_x_unclamped > 0 && end_x_unclamped <= WIDTH {
+            for yy in start_y..end_y {
+                draw_point(stroke, end_x_unclamped - 1, yy);
+            }
+        }
+
+        // Top edge
+        if (0..HEIGHT).contains(&y) {
+            draw_hline_fast(stroke, start_x, y, end_x);
+        }
+
+        // Bottom edge
+        if end_y_unclamped > 0 && end_y_unclamped <= HEIGHT {
+            draw_hline_fast(stroke, start_x, end_y_unclamped - 1, end_x);
+        }
+    }
+}
+
+// Newlines go back to the starting x position, one 8x8 glyph per character
+pub(super) fn text(text: &[u8], x: i32, mut y: i32) {
+    let mut current_x = x;
+    for c in String::from_utf8_lossy(text).chars() {
+        match c as u32 {
+            10 => {
+                y += 8;
+                current_x = x;
+            }
+
+            code @ 32..=255 => {
+                blit_sub(&FONT, current_x, y, 8, 8, 0, (code - 32) << 3, 8, BLIT_1BPP);
+                current_x += 8;
+            }
+
+            _ => current_x += 8,
+        }
+    }
+}
+
+pub(super) fn vline(x: i32, y: i32, len: u32) {
+    let (dc0, _) = draw_colors_01();
+    let end_y = y + len as i32;
+    if end_y <= 0 || !(0..WIDTH).contains(&x) || dc0 == 0 {
+        return;
+    }
+
+    let stroke = (dc0 - 1) & 0b11;
+    for yy in y.max(0)..end_y.min(HEIGHT) {
+        draw_point(stroke, x, yy);
+    }
+}
+
+pub(super) fn hline(x: i32, y: i32, len: u32) {
+    let (dc0, _) = draw_colors_01();
+    if dc0 == 0 {
+        return;
+    }
+
+    let stroke = (dc0 - 1) & 0b11;
+    draw_hline_unclipped(stroke, x, y, x + len as i32);
+}
+
+// No audio device on the host
+pub(super) fn tone(frequency: u32, duration: u32, volume: u32, flags: u32) {}
+
+pub(super) fn trace(text: &str) {
+    eprintln!("{text}");
+}